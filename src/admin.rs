@@ -0,0 +1,188 @@
+use crate::error::ServerError;
+use crate::instance::RunningInstance;
+use crate::version::Version;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Player {
+    pub name: String,
+    pub online: bool,
+}
+
+/// Replies factorio sends instead of executing the command.
+const FAILURE_REPLIES: [&str; 5] = [
+    "doesn't exist",
+    "does not exist",
+    "Unknown command",
+    "Cannot ",
+    "is not allowed",
+];
+
+/// Factorio usernames consist of letters, digits, `-`, `_` and `.`.
+/// Everything else can't be addressed in a command or would be split into multiple arguments.
+fn check_player_name(player: &str) -> Result<(), ServerError> {
+    let valid = !player.is_empty()
+        && player
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+    if !valid {
+        return Err(ServerError::InvalidPlayerName(player.to_string()));
+    }
+    Ok(())
+}
+
+fn check_reply(reply: &str) -> Result<(), ServerError> {
+    if FAILURE_REPLIES
+        .iter()
+        .any(|failure| reply.contains(failure))
+    {
+        return Err(ServerError::CommandFailed(reply.trim().to_string()));
+    }
+    Ok(())
+}
+
+/// Parse the output of `/players`, `/players online` and `/admins`:
+/// ```text
+/// Online players (2):
+///   some_player (online)
+///   other_player (online)
+/// ```
+fn parse_players(reply: &str) -> Vec<Player> {
+    reply
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.ends_with("):"))
+        .map(|line| match line.strip_suffix(" (online)") {
+            Some(name) => Player {
+                name: name.to_string(),
+                online: true,
+            },
+            None => Player {
+                name: line.to_string(),
+                online: false,
+            },
+        })
+        .collect()
+}
+
+impl RunningInstance {
+    async fn player_command(
+        &self,
+        command: &str,
+        player: &str,
+        reason: Option<&str>,
+    ) -> Result<(), ServerError> {
+        check_player_name(player)?;
+
+        let command = match reason {
+            // the reason is the rest of the line, newlines would start a new command
+            Some(reason) => format!("/{} {} {}", command, player, reason.replace('\n', " ")),
+            None => format!("/{} {}", command, player),
+        };
+        check_reply(&self.send_command(&command).await?)
+    }
+
+    pub async fn kick(&self, player: &str, reason: Option<&str>) -> Result<(), ServerError> {
+        self.player_command("kick", player, reason).await
+    }
+
+    pub async fn ban(&self, player: &str, reason: Option<&str>) -> Result<(), ServerError> {
+        self.player_command("ban", player, reason).await
+    }
+
+    pub async fn unban(&self, player: &str) -> Result<(), ServerError> {
+        self.player_command("unban", player, None).await
+    }
+
+    pub async fn promote(&self, player: &str) -> Result<(), ServerError> {
+        self.player_command("promote", player, None).await
+    }
+
+    pub async fn demote(&self, player: &str) -> Result<(), ServerError> {
+        self.player_command("demote", player, None).await
+    }
+
+    pub async fn mute(&self, player: &str) -> Result<(), ServerError> {
+        self.player_command("mute", player, None).await
+    }
+
+    pub async fn unmute(&self, player: &str) -> Result<(), ServerError> {
+        self.player_command("unmute", player, None).await
+    }
+
+    pub async fn whitelist_add(&self, player: &str) -> Result<(), ServerError> {
+        self.player_command("whitelist add", player, None).await
+    }
+
+    pub async fn whitelist_remove(&self, player: &str) -> Result<(), ServerError> {
+        self.player_command("whitelist remove", player, None).await
+    }
+
+    /// All players that ever joined this map.
+    pub async fn players(&self) -> Result<Vec<Player>, ServerError> {
+        Ok(parse_players(&self.query("/players").await?))
+    }
+
+    pub async fn players_online(&self) -> Result<Vec<String>, ServerError> {
+        Ok(parse_players(&self.query("/players online").await?)
+            .into_iter()
+            .map(|player| player.name)
+            .collect())
+    }
+
+    pub async fn admins(&self) -> Result<Vec<Player>, ServerError> {
+        Ok(parse_players(&self.query("/admins").await?))
+    }
+
+    /// Save the map under its current name.
+    pub async fn server_save(&self) -> Result<(), ServerError> {
+        check_reply(&self.send_command("/server-save").await?)
+    }
+
+    pub async fn seed(&self) -> Result<u32, ServerError> {
+        Ok(self.query("/seed").await?.trim().parse()?)
+    }
+
+    pub async fn version(&self) -> Result<Version, ServerError> {
+        self.query("/version").await?.trim().parse()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn player_names() {
+        assert!(check_player_name("some_player-1.2").is_ok());
+        assert!(check_player_name("").is_err());
+        assert!(check_player_name("some player").is_err());
+        assert!(check_player_name("player\n/quit").is_err());
+    }
+
+    #[test]
+    fn players() {
+        assert_eq!(
+            parse_players("Players (2):\n  some_player (online)\n  other_player\n"),
+            vec![
+                Player {
+                    name: "some_player".to_string(),
+                    online: true,
+                },
+                Player {
+                    name: "other_player".to_string(),
+                    online: false,
+                },
+            ]
+        );
+        assert_eq!(parse_players("Online players (0):\n"), vec![]);
+    }
+
+    #[test]
+    fn replies() {
+        assert!(check_reply("").is_ok());
+        assert!(matches!(
+            check_reply("Player nobody doesn't exist."),
+            Err(ServerError::CommandFailed(reply)) if reply == "Player nobody doesn't exist."
+        ));
+    }
+}
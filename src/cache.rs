@@ -1,4 +1,4 @@
-use crate::credentials::CredentialManager;
+use crate::credentials::{CredentialManager, Credentials};
 use crate::error::ServerError;
 use crate::mod_portal::ModPortal;
 use crate::utilities::assure_subdir;
@@ -14,6 +14,7 @@ use std::fs::remove_dir_all;
 use std::path::{Path, PathBuf};
 use tokio::fs::{create_dir_all, File};
 use tokio::io::AsyncWriteExt;
+use tokio::sync::RwLock;
 use tokio::sync::broadcast;
 use tokio::sync::broadcast::{Receiver, Sender};
 use tokio_util::either::Either;
@@ -24,7 +25,7 @@ pub struct Cache {
     root_path: PathBuf,
     factorio_dir: PathBuf,
     mods_dir: PathBuf,
-    credentials: CredentialManager,
+    credentials: RwLock<CredentialManager>,
     mod_portal: ModPortal,
     client: Client,
     in_flight: InFlight,
@@ -55,7 +56,7 @@ impl Cache {
         Ok(Self {
             factorio_dir,
             mods_dir,
-            credentials: CredentialManager::load(root_path.join("credentials.json"))?,
+            credentials: RwLock::new(CredentialManager::load(root_path.join("credentials.json"))?),
             root_path,
             mod_portal: ModPortal::new()?,
             client: Client::new(),
@@ -163,13 +164,13 @@ impl Cache {
         // Download Factorio //
         ///////////////////////
 
-        if !self.credentials.has_token() {
+        if !self.credentials.read().await.has_token() {
             return Err(ServerError::NotAllowed(
                 "Please Login before downloading factorio".to_string(),
             ));
         }
 
-        let credentials = self.credentials.get_credentials()?;
+        let credentials = self.credentials.read().await.get_credentials()?;
         let build = if version >= &Version::from([2, 0, 0]) {
             "expansion"
         } else {
@@ -445,7 +446,7 @@ impl Cache {
                 }
             }
             Either::Right(sender_guard) => {
-                if !self.credentials.has_token() {
+                if !self.credentials.read().await.has_token() {
                     return Err(ServerError::NotAllowed("credentials required".to_string()));
                 }
 
@@ -490,7 +491,7 @@ impl Cache {
         ))?)
         .await?;
 
-        let creds = self.credentials.get_credentials()?;
+        let creds = self.credentials.read().await.get_credentials()?;
         let url = format!(
             "https://mods.factorio.com/{}?username={}&token={}",
             url.as_ref(),
@@ -526,18 +527,24 @@ impl Cache {
     }
 
     pub async fn factorio_login(
-        &mut self,
+        &self,
         username: impl AsRef<str>,
         password: impl AsRef<str>,
     ) -> Result<(), ServerError> {
-        self.credentials.login(username, password).await?;
-        self.credentials.save()?;
+        let mut credentials = self.credentials.write().await;
+        credentials.login(username, password).await?;
+        credentials.save()?;
         Ok(())
     }
 
-    pub async fn factorio_logout(&mut self) -> Result<(), ServerError> {
-        self.credentials.logout();
-        self.credentials.save()?;
+    pub(crate) async fn credentials(&self) -> Result<Credentials, ServerError> {
+        self.credentials.read().await.get_credentials()
+    }
+
+    pub async fn factorio_logout(&self) -> Result<(), ServerError> {
+        let mut credentials = self.credentials.write().await;
+        credentials.logout();
+        credentials.save()?;
         Ok(())
     }
 }
@@ -549,18 +556,20 @@ mod test {
 
     #[tokio::test]
     async fn test() {
-        let mut cache = Cache::new(PathBuf::from("/tmp")).unwrap();
+        let cache = Cache::new(PathBuf::from("/tmp")).unwrap();
         // let mut cache = Cache::new(PathBuf::from("C:\\Data\\tmp\\factorio")).unwrap();
 
         cache
             .credentials
+            .write()
+            .await
             .login(
                 dotenvy::var("factorio_username").unwrap(),
                 dotenvy::var("factorio_password").unwrap(),
             )
             .await
             .unwrap();
-        cache.credentials.save().unwrap();
+        cache.credentials.read().await.save().unwrap();
 
         let mut progress = Progress::new(10000);
 
@@ -0,0 +1,259 @@
+/// A line of `console.log`, e.g. `2024-05-01 12:00:00 [JOIN] some_player joined the game`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ConsoleEvent {
+    /// Local server time as printed by factorio, `YYYY-MM-DD HH:MM:SS`.
+    pub timestamp: String,
+    pub message: ConsoleMessage,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub enum ConsoleMessage {
+    Join {
+        player: String,
+    },
+    Leave {
+        player: String,
+    },
+    Chat {
+        player: String,
+        message: String,
+    },
+    Kick {
+        player: String,
+        by: String,
+        reason: Option<String>,
+    },
+    Ban {
+        player: String,
+        by: String,
+        reason: Option<String>,
+    },
+    Unban {
+        player: String,
+        by: String,
+    },
+    Promote {
+        player: String,
+        by: String,
+    },
+    Demote {
+        player: String,
+        by: String,
+    },
+    /// A console command like `/c` or `/silent-command`, `command` is the name without slash.
+    Command {
+        player: String,
+        command: String,
+        arguments: String,
+    },
+    /// A tagged line we don't know or couldn't parse, e.g. `[WARNING]`.
+    Other {
+        tag: String,
+        text: String,
+    },
+}
+
+impl ConsoleEvent {
+    /// Parse a single console line, returns `None` if it doesn't start with timestamp and tag.
+    pub fn parse(line: &str) -> Option<Self> {
+        let timestamp = line
+            .get(..19)
+            .filter(|timestamp| Self::is_timestamp(timestamp))?;
+        let rest = line.get(19..)?.strip_prefix(" [")?;
+        let (tag, text) = rest.split_once("] ")?;
+
+        let message = Self::parse_message(tag, text).unwrap_or_else(|| ConsoleMessage::Other {
+            tag: tag.to_string(),
+            text: text.to_string(),
+        });
+
+        Some(Self {
+            timestamp: timestamp.to_string(),
+            message,
+        })
+    }
+
+    /// `YYYY-MM-DD HH:MM:SS`
+    fn is_timestamp(timestamp: &str) -> bool {
+        timestamp.len() == 19
+            && timestamp.bytes().enumerate().all(|(i, byte)| match i {
+                4 | 7 => byte == b'-',
+                10 => byte == b' ',
+                13 | 16 => byte == b':',
+                _ => byte.is_ascii_digit(),
+            })
+    }
+
+    fn parse_message(tag: &str, text: &str) -> Option<ConsoleMessage> {
+        let message = match tag {
+            "JOIN" => ConsoleMessage::Join {
+                player: text.split_once(" joined the game")?.0.to_string(),
+            },
+            "LEAVE" => ConsoleMessage::Leave {
+                player: text.split_once(" left the game")?.0.to_string(),
+            },
+            "CHAT" => {
+                let (player, message) = text.split_once(": ")?;
+                ConsoleMessage::Chat {
+                    player: player.to_string(),
+                    message: message.to_string(),
+                }
+            }
+            "KICK" => {
+                let (player, by, reason) = Self::parse_action(text, " was kicked by ")?;
+                ConsoleMessage::Kick { player, by, reason }
+            }
+            "BAN" => {
+                let (player, by, reason) = Self::parse_action(text, " was banned by ")?;
+                ConsoleMessage::Ban { player, by, reason }
+            }
+            "UNBANNED" => {
+                let (player, by, _) = Self::parse_action(text, " was unbanned by ")?;
+                ConsoleMessage::Unban { player, by }
+            }
+            "PROMOTE" => {
+                let (player, by, _) = Self::parse_action(text, " was promoted to admin by ")?;
+                ConsoleMessage::Promote { player, by }
+            }
+            "DEMOTE" => {
+                let (player, by, _) = Self::parse_action(text, " was demoted from admin by ")?;
+                ConsoleMessage::Demote { player, by }
+            }
+            "COMMAND" => {
+                // "some_player (command): game.print(1)"
+                let (player, rest) = text.split_once(" (")?;
+                let (command, arguments) = rest.split_once("): ").or_else(|| {
+                    rest.strip_suffix("):")
+                        .or_else(|| rest.strip_suffix(')'))
+                        .map(|command| (command, ""))
+                })?;
+                ConsoleMessage::Command {
+                    player: player.to_string(),
+                    command: command.to_string(),
+                    arguments: arguments.to_string(),
+                }
+            }
+            _ => return None,
+        };
+
+        Some(message)
+    }
+
+    /// Parse `<player><action><by>.[ Reason: <reason>.]`
+    fn parse_action(text: &str, action: &str) -> Option<(String, String, Option<String>)> {
+        let (player, rest) = text.split_once(action)?;
+        let (by, reason) = match rest.split_once(". Reason: ") {
+            Some((by, reason)) => (by, Some(reason.trim_end_matches('.').to_string())),
+            None => (rest.trim_end_matches('.'), None),
+        };
+
+        Some((player.to_string(), by.to_string(), reason))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn parse(line: &str) -> ConsoleMessage {
+        let event = ConsoleEvent::parse(line).unwrap();
+        assert_eq!(event.timestamp, "2024-05-01 12:00:00");
+        event.message
+    }
+
+    #[test]
+    fn players() {
+        assert_eq!(
+            parse("2024-05-01 12:00:00 [JOIN] some_player joined the game"),
+            ConsoleMessage::Join {
+                player: "some_player".to_string()
+            }
+        );
+        assert_eq!(
+            parse("2024-05-01 12:00:00 [LEAVE] some_player left the game"),
+            ConsoleMessage::Leave {
+                player: "some_player".to_string()
+            }
+        );
+        assert_eq!(
+            parse("2024-05-01 12:00:00 [CHAT] some_player: hello: world"),
+            ConsoleMessage::Chat {
+                player: "some_player".to_string(),
+                message: "hello: world".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn moderation() {
+        assert_eq!(
+            parse(
+                "2024-05-01 12:00:00 [KICK] some_player was kicked by <server>. Reason: too loud."
+            ),
+            ConsoleMessage::Kick {
+                player: "some_player".to_string(),
+                by: "<server>".to_string(),
+                reason: Some("too loud".to_string()),
+            }
+        );
+        assert_eq!(
+            parse("2024-05-01 12:00:00 [BAN] some_player was banned by admin."),
+            ConsoleMessage::Ban {
+                player: "some_player".to_string(),
+                by: "admin".to_string(),
+                reason: None,
+            }
+        );
+        assert_eq!(
+            parse("2024-05-01 12:00:00 [UNBANNED] some_player was unbanned by admin."),
+            ConsoleMessage::Unban {
+                player: "some_player".to_string(),
+                by: "admin".to_string(),
+            }
+        );
+        assert_eq!(
+            parse("2024-05-01 12:00:00 [PROMOTE] some_player was promoted to admin by <server>."),
+            ConsoleMessage::Promote {
+                player: "some_player".to_string(),
+                by: "<server>".to_string(),
+            }
+        );
+        assert_eq!(
+            parse("2024-05-01 12:00:00 [COMMAND] admin (command): game.print(1)"),
+            ConsoleMessage::Command {
+                player: "admin".to_string(),
+                command: "command".to_string(),
+                arguments: "game.print(1)".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn unknown() {
+        assert_eq!(
+            parse("2024-05-01 12:00:00 [WARNING] something happened"),
+            ConsoleMessage::Other {
+                tag: "WARNING".to_string(),
+                text: "something happened".to_string(),
+            }
+        );
+        assert_eq!(ConsoleEvent::parse("   0.001 Info main.cpp:1: hi"), None);
+    }
+
+    #[test]
+    fn no_timestamp() {
+        assert_eq!(
+            ConsoleEvent::parse("abcdefghijklmnopqrs [CHAT] player: hi"),
+            None
+        );
+        assert_eq!(
+            ConsoleEvent::parse("2024-05-01T12:00:00 [CHAT] player: hi"),
+            None
+        );
+        assert_eq!(
+            ConsoleEvent::parse("2024-05-01 12:0x:00 [CHAT] player: hi"),
+            None
+        );
+    }
+}
@@ -1,13 +1,31 @@
 use crate::error::ServerError;
 use crate::utilities::{assure_subdir, get_file_size};
+use rc_zip_tokio::ReadZip;
 use std::fs::remove_file;
 use std::io;
 use std::path::{Path, PathBuf};
-use tokio::fs::{File, create_dir_all};
+use std::time::SystemTime;
+use tokio::fs::{File, create_dir_all, read_dir, remove_dir_all, rename};
+
+const AUTOSAVE_PREFIX: &str = "_autosave";
+
+/// A save in `data/saves/<folder>/<name>.zip`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SaveInfo {
+    /// The saves folder, as used by `InstanceSettings::save`.
+    pub folder: String,
+    /// File name without `.zip`.
+    pub name: String,
+    pub size: u64,
+    pub modified: SystemTime,
+    /// Written by factorio as `_autosave<n>.zip`.
+    pub autosave: bool,
+}
 
 pub(crate) struct Data {
     root_path: PathBuf,
     saves_path: PathBuf,
+    scenarios_path: PathBuf,
     files_path: PathBuf,
 }
 
@@ -16,16 +34,19 @@ impl Data {
         let root_path = root_path.as_ref();
 
         let saves_path = root_path.join("saves");
+        let scenarios_path = root_path.join("scenarios");
         let files_path = root_path.join("files");
 
         // assure that the directories exist
         assure_subdir(&root_path)?;
         assure_subdir(&saves_path)?;
+        assure_subdir(&scenarios_path)?;
         assure_subdir(&files_path)?;
 
         Ok(Self {
             root_path: root_path.into(),
             saves_path,
+            scenarios_path,
             files_path,
         })
     }
@@ -38,6 +59,174 @@ impl Data {
         Ok(path)
     }
 
+    /// Scenarios that are shared by all instances.
+    pub(crate) fn get_scenarios_folder(&self) -> &Path {
+        &self.scenarios_path
+    }
+
+    /// Folder for a new save, it is created if it doesn't exist.
+    pub(crate) async fn create_saves_folder(&self, name: impl AsRef<str>) -> io::Result<PathBuf> {
+        let path = self.saves_path.join(name.as_ref());
+        create_dir_all(&path).await?;
+        Ok(path)
+    }
+
+    pub(crate) fn save_file(&self, folder: &str, name: &str) -> PathBuf {
+        self.saves_path.join(folder).join(format!("{}.zip", name))
+    }
+
+    fn existing_save_file(&self, folder: &str, name: &str) -> Result<PathBuf, ServerError> {
+        let path = self.save_file(folder, name);
+        if !path.exists() {
+            return Err(ServerError::SaveNotFound(format!("{}/{}", folder, name)));
+        }
+        Ok(path)
+    }
+
+    /// Create the folder of a new save, fails if the save exists already.
+    async fn new_save_file(&self, folder: &str, name: &str) -> Result<PathBuf, ServerError> {
+        let path = self
+            .create_saves_folder(folder)
+            .await?
+            .join(format!("{}.zip", name));
+        if path.exists() {
+            return Err(ServerError::NotAllowed(format!(
+                "Save {}/{} already exists",
+                folder, name
+            )));
+        }
+        Ok(path)
+    }
+
+    /// Names of all saves folders, sorted.
+    pub(crate) async fn list_save_folders(&self) -> io::Result<Vec<String>> {
+        let mut names = vec![];
+        let mut entries = read_dir(&self.saves_path).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            if entry.file_type().await?.is_dir()
+                && let Some(name) = entry.file_name().to_str()
+            {
+                names.push(name.to_string());
+            }
+        }
+        names.sort();
+        Ok(names)
+    }
+
+    /// All saves in a folder, the newest first.
+    pub(crate) async fn list_saves(&self, folder: &str) -> Result<Vec<SaveInfo>, ServerError> {
+        let mut saves = vec![];
+        let mut entries = read_dir(self.get_saves_folder(&folder.to_string())?).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            let metadata = entry.metadata().await?;
+            if !metadata.is_file() || path.extension().is_none_or(|ext| ext != "zip") {
+                continue;
+            }
+            let Some(name) = path.file_stem().and_then(|name| name.to_str()) else {
+                continue;
+            };
+
+            saves.push(SaveInfo {
+                folder: folder.to_string(),
+                name: name.to_string(),
+                modified: metadata.modified()?,
+                size: get_file_size(metadata),
+                autosave: name.starts_with(AUTOSAVE_PREFIX),
+            });
+        }
+        saves.sort_by(|a, b| {
+            b.modified
+                .cmp(&a.modified)
+                .then_with(|| a.name.cmp(&b.name))
+        });
+        Ok(saves)
+    }
+
+    /// Copy a save into the library after checking that it is a factorio save.
+    pub(crate) async fn import_save(
+        &self,
+        folder: &str,
+        name: &str,
+        source: &Path,
+    ) -> Result<PathBuf, ServerError> {
+        let zip = tokio::fs::read(source).await?;
+        let reader = zip.read_zip().await?;
+        // the map is stored as `<name>/level.dat0`, or `<name>/level.dat` before 0.17
+        let is_save = reader.entries().any(|entry| {
+            entry
+                .sanitized_name()
+                .and_then(|name| Path::new(name).file_name())
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.starts_with("level.dat"))
+        });
+        if !is_save {
+            return Err(ServerError::InvalidSave(source.display().to_string()));
+        }
+
+        let path = self.new_save_file(folder, name).await?;
+        tokio::fs::write(&path, zip).await?;
+        Ok(path)
+    }
+
+    pub(crate) async fn export_save(
+        &self,
+        folder: &str,
+        name: &str,
+        destination: &Path,
+    ) -> Result<(), ServerError> {
+        tokio::fs::copy(self.existing_save_file(folder, name)?, destination).await?;
+        Ok(())
+    }
+
+    pub(crate) async fn delete_save(&self, folder: &str, name: &str) -> Result<(), ServerError> {
+        tokio::fs::remove_file(self.existing_save_file(folder, name)?).await?;
+        Ok(())
+    }
+
+    pub(crate) async fn rename_save(
+        &self,
+        folder: &str,
+        name: &str,
+        new_name: &str,
+    ) -> Result<(), ServerError> {
+        let path = self.existing_save_file(folder, name)?;
+        rename(path, self.new_save_file(folder, new_name).await?).await?;
+        Ok(())
+    }
+
+    pub(crate) async fn copy_save(
+        &self,
+        folder: &str,
+        name: &str,
+        to_folder: &str,
+        new_name: &str,
+    ) -> Result<PathBuf, ServerError> {
+        let path = self.existing_save_file(folder, name)?;
+        let new_path = self.new_save_file(to_folder, new_name).await?;
+        tokio::fs::copy(path, &new_path).await?;
+        Ok(new_path)
+    }
+
+    /// Delete all but the `keep` newest autosaves of a folder, returns the deleted ones.
+    pub(crate) async fn prune_autosaves(
+        &self,
+        folder: &str,
+        keep: usize,
+    ) -> Result<Vec<SaveInfo>, ServerError> {
+        let pruned: Vec<_> = self
+            .list_saves(folder)
+            .await?
+            .into_iter()
+            .filter(|save| save.autosave)
+            .skip(keep)
+            .collect();
+        for save in &pruned {
+            tokio::fs::remove_file(self.save_file(folder, &save.name)).await?;
+        }
+        Ok(pruned)
+    }
+
     fn file_add_number(file: impl AsRef<Path>, num: u8) -> PathBuf {
         let mut file = file.as_ref().as_os_str().to_os_string();
         file.push(format!(".{}", num));
@@ -85,6 +274,27 @@ impl Data {
         Ok(file_path)
     }
 
+    /// Move the files into the data dir of the instance, older versions are rotated.
+    pub(crate) async fn backup_files(
+        &self,
+        instance_name: impl AsRef<str>,
+        paths: Vec<impl AsRef<Path>>,
+    ) -> Result<(), ServerError> {
+        for path in paths {
+            let path = path.as_ref();
+            if path.exists()
+                && let Some(filename) = path.file_name()
+            {
+                let rotated_log = self
+                    .get_and_rotate_file(instance_name.as_ref(), filename.to_str().unwrap(), 9)
+                    .await?;
+                rename(path, &rotated_log).await?;
+            }
+        }
+
+        Ok(())
+    }
+
     pub(crate) async fn get_file(
         &self,
         instance_name: impl AsRef<str>,
@@ -94,4 +304,206 @@ impl Data {
         create_dir_all(&instance_path).await?;
         Ok(instance_path.join(file_name.as_ref()))
     }
+
+    /// Path of a file of the instance, without creating its folder.
+    pub(crate) fn get_file_path(
+        &self,
+        instance_name: impl AsRef<str>,
+        file_name: impl AsRef<str>,
+    ) -> PathBuf {
+        self.files_path
+            .join(instance_name.as_ref())
+            .join(file_name.as_ref())
+    }
+
+    /// Names of all instances that have a file called `file_name`, sorted.
+    pub(crate) async fn list_instances_with(
+        &self,
+        file_name: impl AsRef<str>,
+    ) -> io::Result<Vec<String>> {
+        let mut names = vec![];
+        let mut entries = read_dir(&self.files_path).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            if entry.path().join(file_name.as_ref()).exists()
+                && let Some(name) = entry.file_name().to_str()
+            {
+                names.push(name.to_string());
+            }
+        }
+        names.sort();
+        Ok(names)
+    }
+
+    /// Delete everything stored for the instance.
+    pub(crate) async fn remove_files(&self, instance_name: impl AsRef<str>) -> io::Result<()> {
+        let instance_path = self.files_path.join(instance_name.as_ref());
+        if instance_path.exists() {
+            remove_dir_all(instance_path).await?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand::Rng;
+    use rand::distr::Alphanumeric;
+    use std::fs::File as StdFile;
+    use std::time::Duration;
+
+    /// A fresh library in the temp directory, removed again by `cleanup`.
+    fn data() -> Data {
+        let suffix: String = rand::rng()
+            .sample_iter(&Alphanumeric)
+            .take(8)
+            .map(char::from)
+            .collect();
+        Data::new(std::env::temp_dir().join(format!("factorio_server-data-{}", suffix))).unwrap()
+    }
+
+    fn cleanup(data: Data) {
+        std::fs::remove_dir_all(data.root_path).unwrap();
+    }
+
+    /// Write `<folder>/<name>.zip`, modified `age` ago.
+    fn write_save(data: &Data, folder: &str, name: &str, content: &[u8], age: Duration) {
+        std::fs::create_dir_all(data.saves_path.join(folder)).unwrap();
+        let path = data.save_file(folder, name);
+        std::fs::write(&path, content).unwrap();
+        StdFile::options()
+            .write(true)
+            .open(path)
+            .unwrap()
+            .set_modified(SystemTime::now() - age)
+            .unwrap();
+    }
+
+    /// A zip with a single stored, empty file.
+    fn zip_with(file_name: &str) -> Vec<u8> {
+        let name = file_name.as_bytes();
+        let crc = crc32fast::hash(&[]).to_le_bytes();
+        let mut zip = vec![];
+        zip.extend(0x04034b50u32.to_le_bytes());
+        zip.extend([20, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        zip.extend(crc);
+        zip.extend([0; 8]);
+        zip.extend((name.len() as u16).to_le_bytes());
+        zip.extend([0, 0]);
+        zip.extend(name);
+
+        let central_directory = zip.len() as u32;
+        zip.extend(0x02014b50u32.to_le_bytes());
+        zip.extend([20, 0, 20, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        zip.extend(crc);
+        zip.extend([0; 8]);
+        zip.extend((name.len() as u16).to_le_bytes());
+        zip.extend([0; 12]);
+        zip.extend(0u32.to_le_bytes());
+        zip.extend(name);
+        let central_directory_size = zip.len() as u32 - central_directory;
+
+        zip.extend(0x06054b50u32.to_le_bytes());
+        zip.extend([0, 0, 0, 0, 1, 0, 1, 0]);
+        zip.extend(central_directory_size.to_le_bytes());
+        zip.extend(central_directory.to_le_bytes());
+        zip.extend([0, 0]);
+        zip
+    }
+
+    #[tokio::test]
+    async fn import_rejects_non_saves() {
+        let data = data();
+        let source = data.root_path.join("source.zip");
+
+        std::fs::write(&source, b"not a zip").unwrap();
+        assert!(
+            data.import_save("world", "imported", &source)
+                .await
+                .is_err()
+        );
+
+        std::fs::write(&source, zip_with("world/readme.txt")).unwrap();
+        assert!(matches!(
+            data.import_save("world", "imported", &source).await,
+            Err(ServerError::InvalidSave(_))
+        ));
+        assert!(!data.save_file("world", "imported").exists());
+
+        cleanup(data);
+    }
+
+    #[tokio::test]
+    async fn list_saves() {
+        let data = data();
+        write_save(&data, "world", "world", b"manual", Duration::from_secs(60));
+        write_save(&data, "world", "_autosave1", b"auto", Duration::ZERO);
+        std::fs::write(data.saves_path.join("world").join("notes.txt"), b"").unwrap();
+
+        let saves = data.list_saves("world").await.unwrap();
+        let saves: Vec<_> = saves
+            .iter()
+            .map(|save| (save.name.as_str(), save.size, save.autosave))
+            .collect();
+        assert_eq!(saves, [("_autosave1", 4, true), ("world", 6, false)]);
+
+        cleanup(data);
+    }
+
+    #[tokio::test]
+    async fn prune_autosaves() {
+        let data = data();
+        write_save(&data, "world", "world", b"", Duration::from_secs(3600));
+        for i in 1..=5u64 {
+            // _autosave5 is the newest
+            let age = Duration::from_secs(600 - i * 60);
+            write_save(&data, "world", &format!("_autosave{}", i), b"", age);
+        }
+
+        let pruned = data.prune_autosaves("world", 2).await.unwrap();
+        let mut pruned: Vec<_> = pruned.into_iter().map(|save| save.name).collect();
+        pruned.sort();
+        assert_eq!(pruned, ["_autosave1", "_autosave2", "_autosave3"]);
+
+        let left: Vec<_> = data
+            .list_saves("world")
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|save| save.name)
+            .collect();
+        assert_eq!(left, ["_autosave5", "_autosave4", "world"]);
+
+        cleanup(data);
+    }
+
+    #[tokio::test]
+    async fn no_overwrite() {
+        let data = data();
+        write_save(&data, "world", "a", b"a", Duration::ZERO);
+        write_save(&data, "world", "b", b"b", Duration::ZERO);
+        write_save(&data, "other", "a", b"other", Duration::ZERO);
+
+        assert!(matches!(
+            data.rename_save("world", "a", "b").await,
+            Err(ServerError::NotAllowed(_))
+        ));
+        assert!(matches!(
+            data.copy_save("world", "a", "world", "b").await,
+            Err(ServerError::NotAllowed(_))
+        ));
+        assert!(matches!(
+            data.copy_save("world", "b", "other", "a").await,
+            Err(ServerError::NotAllowed(_))
+        ));
+
+        assert_eq!(std::fs::read(data.save_file("world", "a")).unwrap(), b"a");
+        assert_eq!(std::fs::read(data.save_file("world", "b")).unwrap(), b"b");
+        assert_eq!(
+            std::fs::read(data.save_file("other", "a")).unwrap(),
+            b"other"
+        );
+
+        cleanup(data);
+    }
 }
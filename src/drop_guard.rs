@@ -1,8 +1,6 @@
 use std::future::Future;
 use std::marker::PhantomData;
 use tokio::runtime::Handle;
-use tokio::task;
-use tokio::task::JoinHandle;
 
 pub struct DropGuard<'a, T: FnOnce() -> U + 'a, U> {
     f: Option<T>,
@@ -22,17 +20,25 @@ impl<'a, T: FnOnce() -> U + 'a, U> DropGuard<'a, T, U> {
     }
 }
 
-pub fn new_async<F: Future + Send + 'static>(
-    f: F,
-) -> DropGuard<'static, impl FnOnce() -> JoinHandle<()>, JoinHandle<()>> {
-    let run = move || {
-        task::spawn_blocking(move || {
-            Handle::current().block_on(f);
-        })
+/// Boxed, so it can be stored in a struct.
+pub type AsyncDropGuard = DropGuard<'static, Box<dyn FnOnce() + Send>, ()>;
+
+/// Spawns `f` on the current runtime when dropped, or calls `fallback` if there is no runtime.
+/// Tasks spawned while the runtime shuts down never run, so `f` is lost in that case.
+pub fn new_async<F, B>(f: F, fallback: B) -> AsyncDropGuard
+where
+    F: Future<Output = ()> + Send + 'static,
+    B: FnOnce() + Send + 'static,
+{
+    let run = move || match Handle::try_current() {
+        Ok(handle) => {
+            handle.spawn(f);
+        }
+        Err(_) => fallback(),
     };
 
     DropGuard {
-        f: Some(run),
+        f: Some(Box::new(run)),
         _phantom: PhantomData,
     }
 }
@@ -44,3 +50,26 @@ impl<'a, T: FnOnce() -> U + 'a, U> Drop for DropGuard<'a, T, U> {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    #[test]
+    fn fallback_without_runtime() {
+        let spawned = Arc::new(AtomicBool::new(false));
+        let fallback = Arc::new(AtomicBool::new(false));
+
+        let task_spawned = spawned.clone();
+        let fallback_called = fallback.clone();
+        drop(new_async(
+            async move { task_spawned.store(true, Ordering::SeqCst) },
+            move || fallback_called.store(true, Ordering::SeqCst),
+        ));
+
+        assert!(!spawned.load(Ordering::SeqCst));
+        assert!(fallback.load(Ordering::SeqCst));
+    }
+}
@@ -1,5 +1,6 @@
 use crate::credentials::CredentialsFailure;
-use crate::instance::Status;
+use crate::event::ServerEvent;
+use crate::instance::{StartError, Status};
 use std::num::ParseIntError;
 use thiserror::Error;
 use tokio::sync::broadcast::error::{RecvError, SendError};
@@ -13,12 +14,16 @@ pub enum ServerError {
     IO(#[from] std::io::Error),
     #[error("rcon error: {0}")]
     Rcon(#[from] rcon::Error),
+    #[error("rcon timeout")]
+    RconTimeout,
+    #[error("rcon unavailable: {0}")]
+    RconUnavailable(String),
     #[error("tokio recv error: {0}")]
     TokioRecv(#[from] RecvError),
     #[error("utf-8 error")]
     Utf8Error(),
     #[error("send error: {0}")]
-    TrackerSendError(#[from] SendError<String>),
+    TrackerSendError(#[from] SendError<ServerEvent>),
     #[error("watch status channel send error: {0}")]
     WatchChannelSendError(#[from] tokio::sync::watch::error::SendError<Status>),
     #[error("watch status channel recv error: {0}")]
@@ -41,4 +46,30 @@ pub enum ServerError {
     AlreadyRunningError,
     #[error("Invalid Version Format: {0}")]
     InvalidVersionFormat(String),
+    #[error("Lua Error: {0}")]
+    LuaError(String),
+    #[error("Invalid Player Name: {0}")]
+    InvalidPlayerName(String),
+    #[error("Command Failed: {0}")]
+    CommandFailed(String),
+    #[error("Invalid Address: {0}")]
+    InvalidAddress(String),
+    #[error("Invalid Instance Name: {0}")]
+    InvalidInstanceName(String),
+    #[error("Instance Not Found: {0}")]
+    InstanceNotFound(String),
+    #[error("Instance Already Running: {0}")]
+    InstanceAlreadyRunning(String),
+    #[error("Invalid Save Name: {0}")]
+    InvalidSaveName(String),
+    #[error("Creating the save failed: {0}")]
+    CreateSaveFailed(String),
+    #[error("Save Not Found: {0}")]
+    SaveNotFound(String),
+    #[error("Not a factorio save: {0}")]
+    InvalidSave(String),
+    #[error("Invalid Map Exchange String: {0}")]
+    InvalidMapExchangeString(String),
+    #[error("Start Error: {0}")]
+    StartError(#[from] StartError),
 }
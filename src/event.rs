@@ -0,0 +1,213 @@
+use crate::console_log::ConsoleEvent;
+use crate::log_parser::LogRecord;
+use crate::mod_load_error::ModLoadError;
+use std::fmt::{Display, Formatter};
+use std::path::Path;
+use std::str::FromStr;
+
+/// States of factorio's multiplayer manager, as printed in `changing state from(..) to(..)`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum GameState {
+    Ready,
+    PreparedToHostGame,
+    CreatingGame,
+    InGame,
+    InGameSavingMap,
+    DisconnectingScheduled,
+    Disconnecting,
+    Disconnected,
+    Closed,
+    Other(String),
+}
+
+impl FromStr for GameState {
+    type Err = std::convert::Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "Ready" => Self::Ready,
+            "PreparedToHostGame" => Self::PreparedToHostGame,
+            "CreatingGame" => Self::CreatingGame,
+            "InGame" => Self::InGame,
+            "InGameSavingMap" => Self::InGameSavingMap,
+            "DisconnectingScheduled" => Self::DisconnectingScheduled,
+            "Disconnecting" => Self::Disconnecting,
+            "Disconnected" => Self::Disconnected,
+            "Closed" => Self::Closed,
+            other => Self::Other(other.to_string()),
+        })
+    }
+}
+
+impl Display for GameState {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Self::Ready => "Ready",
+            Self::PreparedToHostGame => "PreparedToHostGame",
+            Self::CreatingGame => "CreatingGame",
+            Self::InGame => "InGame",
+            Self::InGameSavingMap => "InGameSavingMap",
+            Self::DisconnectingScheduled => "DisconnectingScheduled",
+            Self::Disconnecting => "Disconnecting",
+            Self::Disconnected => "Disconnected",
+            Self::Closed => "Closed",
+            Self::Other(other) => other,
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Something that happened on a running factorio server.
+///
+/// Subscribe to these with `RunningInstance::subscribe`.
+#[derive(Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub enum ServerEvent {
+    /// The multiplayer manager changed its state.
+    StateChanged {
+        from: GameState,
+        to: GameState,
+    },
+    /// Factorio failed to load the configured mods.
+    ModLoadError(ModLoadError),
+    /// A save (manual or autosave) was started. `name` is the save name without extension.
+    SaveStarted {
+        name: String,
+    },
+    SaveFinished,
+    /// A line of `console.log`: chat, players joining and leaving, moderation and commands.
+    Console(ConsoleEvent),
+    /// The factorio process is gone. The exit code is only known if it could be collected.
+    ProcessExited {
+        exit_code: Option<i32>,
+    },
+    /// The `DropPolicy` couldn't be applied after all handles were dropped.
+    DropPolicyFailed {
+        error: String,
+    },
+    /// The logs of a crashed server couldn't be backed up before the restart.
+    LogBackupFailed {
+        error: String,
+    },
+    /// A restart attempt after a crash failed, it is retried according to the `RestartPolicy`.
+    RestartFailed {
+        error: String,
+    },
+    /// Files are checked every second because no file watcher is available.
+    FileWatcherUnavailable {
+        error: String,
+    },
+    /// A log entry that didn't match any known event.
+    Unparsed(LogRecord),
+}
+
+impl ServerEvent {
+    pub(crate) fn from_record(record: LogRecord) -> Self {
+        let line = record.message.as_str();
+
+        if let Some(event) = Self::parse_state_change(line) {
+            return event;
+        }
+
+        if let Some(error) = ModLoadError::parse(line) {
+            return Self::ModLoadError(error);
+        }
+
+        if let Some(path) = line.strip_prefix("Saving game as ") {
+            let path = path.trim();
+            let name = Path::new(path)
+                .file_stem()
+                .and_then(|name| name.to_str())
+                .unwrap_or(path);
+            return Self::SaveStarted {
+                name: name.to_string(),
+            };
+        }
+
+        // autosaves: "Saving to _autosave1 (non-blocking)."
+        if let Some(rest) = line.strip_prefix("Saving to ") {
+            let name = rest
+                .split(" (")
+                .next()
+                .unwrap_or(rest)
+                .trim_end_matches('.');
+            return Self::SaveStarted {
+                name: name.to_string(),
+            };
+        }
+
+        if line == "Saving finished" {
+            return Self::SaveFinished;
+        }
+
+        Self::Unparsed(record)
+    }
+
+    fn parse_state_change(line: &str) -> Option<Self> {
+        let pos = line.find("changing state from(")?;
+        let rest = &line[pos + "changing state from(".len()..];
+        let (from, rest) = rest.split_once(')')?;
+        let to = rest.trim_start().strip_prefix("to(")?.strip_suffix(')')?;
+
+        Some(Self::StateChanged {
+            from: from.parse().unwrap(),
+            to: to.parse().unwrap(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::log_parser::LogParser;
+
+    fn parse(line: &str) -> ServerEvent {
+        let mut parser = LogParser::new();
+        parser.push(line);
+        ServerEvent::from_record(parser.flush().unwrap())
+    }
+
+    #[test]
+    fn state_change() {
+        let event = parse(
+            "   2.345 Info ServerMultiplayerManager.cpp:808: updateTick(4294967295) changing state from(CreatingGame) to(InGame)",
+        );
+        assert_eq!(
+            event,
+            ServerEvent::StateChanged {
+                from: GameState::CreatingGame,
+                to: GameState::InGame,
+            }
+        );
+    }
+
+    #[test]
+    fn saves() {
+        assert_eq!(
+            parse(
+                " 300.000 Info AppManagerStates.cpp:2067: Saving game as /srv/factorio/saves/world.zip"
+            ),
+            ServerEvent::SaveStarted {
+                name: "world".to_string()
+            }
+        );
+        assert_eq!(
+            parse(" 600.000 Info AutosaveManager.cpp:75: Saving to _autosave1 (non-blocking)."),
+            ServerEvent::SaveStarted {
+                name: "_autosave1".to_string()
+            }
+        );
+        assert_eq!(
+            parse(" 600.100 Info AppManagerStates.cpp:2067: Saving finished"),
+            ServerEvent::SaveFinished
+        );
+    }
+
+    #[test]
+    fn unparsed() {
+        assert!(matches!(
+            parse("   0.001 Info main.cpp:740: Running in headless mode"),
+            ServerEvent::Unparsed(LogRecord { message, .. }) if message == "Running in headless mode"
+        ));
+    }
+}
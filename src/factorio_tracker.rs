@@ -1,112 +1,382 @@
+use crate::console_log::ConsoleEvent;
 use crate::error::ServerError;
+use crate::event::ServerEvent;
+use crate::log_parser::LogParser;
+use crate::process::ProcessState;
 use crate::utilities::get_file_size;
+use notify::{Event, RecommendedWatcher, RecursiveMode, Watcher};
+use std::collections::VecDeque;
 use std::io::SeekFrom::Start;
-use std::path::Path;
-use std::str::FromStr;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
-use sysinfo::{Pid, RefreshKind, System};
 use tokio::fs::File;
-use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncSeekExt, BufReader};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncSeekExt, BufReader};
+use tokio::process::{ChildStderr, ChildStdout};
 use tokio::sync::broadcast::Sender;
+use tokio::sync::mpsc::{UnboundedReceiver, unbounded_channel};
+use tokio::sync::watch;
 use tokio::task::JoinHandle;
+use tokio::time::{Instant, Interval, sleep_until, timeout};
+
+const HISTORY_LINES: usize = 50;
+/// A multi-line log entry is complete once nothing was logged for this long.
+const PENDING_TIMEOUT: Duration = Duration::from_millis(500);
 
 pub(crate) struct FactorioTracker {
     handle: Option<JoinHandle<Result<(), ServerError>>>,
+    history: LogHistory,
+}
+
+/// The last lines of the factorio log, to explain why something failed.
+#[derive(Clone, Default)]
+struct LogHistory(Arc<Mutex<VecDeque<String>>>);
+
+impl LogHistory {
+    fn push(&self, line: &str) {
+        let mut lines = self.0.lock().unwrap();
+        if lines.len() == HISTORY_LINES {
+            lines.pop_front();
+        }
+        lines.push_back(line.to_string());
+    }
+
+    fn lines(&self) -> Vec<String> {
+        self.0.lock().unwrap().iter().cloned().collect()
+    }
+}
+
+/// Follows a text file that is appended to, like `tail -f`.
+struct FileTail {
+    path: PathBuf,
     file_pos: u64,
     last_size: u64,
 }
 
-impl FactorioTracker {
-    pub(crate) fn watch(
-        factorio_log: impl AsRef<Path> + Send + Sync + 'static,
-        factorio_pid: impl AsRef<Path> + Send + Sync + 'static,
-        sender: Sender<String>,
-    ) -> Self {
-        let mut this = Self {
-            handle: None,
+impl FileTail {
+    fn new(path: impl AsRef<Path>) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
             file_pos: 0,
             last_size: 0,
+        }
+    }
+
+    /// Only follow what is appended from now on.
+    fn from_end(path: impl AsRef<Path>) -> Self {
+        let size = std::fs::metadata(path.as_ref())
+            .map(get_file_size)
+            .unwrap_or_default();
+        Self {
+            path: path.as_ref().to_path_buf(),
+            file_pos: size,
+            last_size: size,
+        }
+    }
+
+    /// Read all complete lines that were appended since the last call.
+    /// Starts from the beginning if the file got smaller, e.g. because it was rotated.
+    async fn read_new_lines(&mut self) -> Result<Vec<String>, ServerError> {
+        // the file doesn't exist until factorio starts writing to it
+        let Ok(mut file) = File::open(&self.path).await else {
+            return Ok(vec![]);
         };
 
-        let t = tokio::spawn(async move {
-            let mut interval = tokio::time::interval(Duration::from_secs(1));
-            // let mut interval = tokio::time::interval(Duration::from_millis(10));
+        let size = get_file_size(file.metadata().await?);
+        if size < self.last_size {
+            self.last_size = 0;
+            self.file_pos = 0;
+        }
+        if size == self.last_size {
+            return Ok(vec![]);
+        }
+        self.last_size = size;
 
-            'outer: loop {
-                'waiter: loop {
-                    // check if file already exists and if not, we don't need to wait for a smaller filesize.
-                    // and we can skip reading the file xD
-                    if !factorio_log.as_ref().exists() {
-                        break;
-                    }
+        file.seek(Start(self.file_pos)).await?;
+        let mut reader = BufReader::new(file);
+
+        let mut lines = vec![];
+        loop {
+            let mut out = String::new();
+            let read = reader.read_line(&mut out).await?;
+            // stop at EOF and on lines that are not completely written yet
+            if read == 0 || !out.ends_with('\n') {
+                break;
+            }
+            self.file_pos += read as u64;
 
-                    // check if file size changed
-                    if let Ok(mut file) = File::open(&factorio_log).await {
-                        let metadata = file.metadata().await?;
-                        let size = get_file_size(metadata);
+            out.pop();
+            if out.ends_with('\r') {
+                out.pop();
+            }
+            lines.push(out);
+        }
 
-                        if size < this.last_size {
-                            // file got smaller, read whole file
-                            this.last_size = 0;
-                            this.file_pos = 0;
-                        }
+        Ok(lines)
+    }
+}
+
+/// Wakes the tracker whenever files in the instance directory change.
+/// Falls back to checking every second if no file watcher is available.
+enum Wakeup {
+    Notify {
+        // has to be kept alive for the events to be delivered
+        _watcher: RecommendedWatcher,
+        events: UnboundedReceiver<()>,
+    },
+    Poll(Interval),
+}
+
+impl Wakeup {
+    fn new(dir: impl AsRef<Path>, sender: &Sender<ServerEvent>) -> Self {
+        match Self::watch(dir) {
+            Ok(wakeup) => wakeup,
+            Err(err) => {
+                sender
+                    .send(ServerEvent::FileWatcherUnavailable {
+                        error: err.to_string(),
+                    })
+                    .ok();
+                Self::Poll(tokio::time::interval(Duration::from_secs(1)))
+            }
+        }
+    }
+
+    fn watch(dir: impl AsRef<Path>) -> notify::Result<Self> {
+        let (sender, events) = unbounded_channel();
+        // read everything that was written before the watcher was set up
+        sender.send(()).ok();
 
-                        if size > this.last_size {
-                            println!("file got bigger, read lines...");
-                            // file got bigger, read lines
-                            this.last_size = size;
+        let mut watcher = notify::recommended_watcher(move |event: notify::Result<Event>| {
+            if event.is_ok() {
+                sender.send(()).ok();
+            }
+        })?;
+        watcher.watch(dir.as_ref(), RecursiveMode::NonRecursive)?;
+
+        Ok(Self::Notify {
+            _watcher: watcher,
+            events,
+        })
+    }
 
-                            loop {
-                                file.seek(Start(this.file_pos)).await?;
+    async fn wait(&mut self) {
+        match self {
+            Self::Notify { events, .. } => {
+                if events.recv().await.is_none() {
+                    // watcher is gone, nothing will wake us anymore
+                    std::future::pending::<()>().await;
+                }
+                // a single write causes multiple notifications, handle them all at once
+                while events.try_recv().is_ok() {}
+            }
+            Self::Poll(interval) => {
+                interval.tick().await;
+            }
+        }
+    }
+}
 
-                                let mut file_buf = BufReader::new(&mut file);
+/// Where the factorio log is read from.
+pub(crate) enum LogSource {
+    /// Tail `factorio-current.log`.
+    File(PathBuf),
+    /// Tail `factorio-current.log` of a server that was already running, old entries are skipped.
+    Resume(PathBuf),
+    /// Read the process output directly, this includes everything printed before the log file exists.
+    Output {
+        stdout: ChildStdout,
+        stderr: ChildStderr,
+    },
+}
 
-                                let mut out = String::new();
-                                let read = file_buf.read_line(&mut out).await?;
-                                if read == 0 {
-                                    // EOF reached, we do nothing more here
-                                    // also happens if nothing is read :D
-                                    break 'waiter;
-                                } else {
-                                    this.file_pos += read as u64;
-                                }
+impl FactorioTracker {
+    pub(crate) fn watch(
+        log_source: LogSource,
+        console_log: impl AsRef<Path> + Send + Sync + 'static,
+        mut process: watch::Receiver<ProcessState>,
+        sender: Sender<ServerEvent>,
+    ) -> Self {
+        let history = LogHistory::default();
+        let tracker_history = history.clone();
 
-                                if out.ends_with('\n') {
-                                    out.pop();
+        let handle = tokio::spawn(async move {
+            let history = tracker_history;
+            let mut wakeup = Wakeup::new(
+                console_log.as_ref().parent().unwrap_or(Path::new(".")),
+                &sender,
+            );
+            let mut console_log = match log_source {
+                LogSource::Resume(_) => FileTail::from_end(console_log),
+                _ => FileTail::new(console_log),
+            };
+            let mut parser = LogParser::new();
+            let mut last_line_at = Instant::now();
 
-                                    if out.ends_with('\r') {
-                                        out.pop();
-                                    }
-                                }
+            let (mut factorio_log, output_readers) = match log_source {
+                LogSource::File(path) => (Some(FileTail::new(path)), vec![]),
+                LogSource::Resume(path) => (Some(FileTail::from_end(path)), vec![]),
+                LogSource::Output { stdout, stderr } => (
+                    None,
+                    vec![
+                        tokio::spawn(Self::read_output(stdout, sender.clone(), history.clone())),
+                        tokio::spawn(Self::read_output(stderr, sender.clone(), history.clone())),
+                    ],
+                ),
+            };
 
-                                sender.send(out)?;
-                            }
+            loop {
+                let exited = tokio::select! {
+                    _ = wakeup.wait() => None,
+                    state = process.wait_for(|state| matches!(state, ProcessState::Exited(_))) => {
+                        match *state? {
+                            ProcessState::Exited(exit_code) => Some(exit_code),
+                            ProcessState::Running => None,
                         }
-                    } else {
-                        break;
                     }
+                    _ = sleep_until(last_line_at + PENDING_TIMEOUT), if parser.has_pending() => None,
+                };
 
-                    // check if factorio is still running
-                    if let Ok(mut file) = File::open(factorio_pid.as_ref()).await {
-                        let mut buf = String::new();
-                        file.read_to_string(&mut buf).await?;
-                        let pid = Pid::from_str(&buf)?;
-
-                        let system = System::new_with_specifics(RefreshKind::everything());
-                        let process = system.process(pid);
-                        if process.is_none() {
-                            sender.send(String::from("factorio process stopped"))?;
-                            break 'outer;
+                if let Some(factorio_log) = &mut factorio_log {
+                    let lines = factorio_log.read_new_lines().await?;
+                    if !lines.is_empty() {
+                        last_line_at = Instant::now();
+                    }
+                    for line in lines {
+                        history.push(&line);
+                        if let Some(record) = parser.push(&line) {
+                            sender.send(ServerEvent::from_record(record))?;
                         }
                     }
+                    // continuation lines can be read separately from their header,
+                    // so the pending entry is only complete after a quiet period or on exit
+                    if (exited.is_some() || last_line_at.elapsed() >= PENDING_TIMEOUT)
+                        && let Some(record) = parser.flush()
+                    {
+                        sender.send(ServerEvent::from_record(record))?;
+                    }
+                }
+
+                for line in console_log.read_new_lines().await? {
+                    if let Some(event) = ConsoleEvent::parse(&line) {
+                        sender.send(ServerEvent::Console(event))?;
+                    }
+                }
+
+                // only report the exit after everything the process wrote was read
+                if let Some(exit_code) = exited {
+                    for reader in output_readers {
+                        // the pipes are closed with the process, so this doesn't block
+                        reader.await.ok();
+                    }
+                    sender.send(ServerEvent::ProcessExited { exit_code })?;
+                    break;
                 }
-                interval.tick().await;
             }
+
             Ok(())
         });
 
-        this.handle = Some(t);
+        Self {
+            handle: Some(handle),
+            history,
+        }
+    }
+
+    /// The last lines factorio logged.
+    pub(crate) fn last_lines(&self) -> Vec<String> {
+        self.history.lines()
+    }
+
+    /// Parse the output of stdout or stderr until the pipe is closed.
+    async fn read_output(
+        output: impl AsyncRead + Unpin,
+        sender: Sender<ServerEvent>,
+        history: LogHistory,
+    ) {
+        let mut lines = BufReader::new(output).lines();
+        let mut parser = LogParser::new();
+
+        // Send errors are ignored, the pipe has to be drained or factorio blocks on writing.
+        loop {
+            // the pending entry is complete once nothing was written for a while
+            let line = if parser.has_pending() {
+                match timeout(PENDING_TIMEOUT, lines.next_line()).await {
+                    Ok(line) => line,
+                    Err(_) => {
+                        if let Some(record) = parser.flush() {
+                            sender.send(ServerEvent::from_record(record)).ok();
+                        }
+                        continue;
+                    }
+                }
+            } else {
+                lines.next_line().await
+            };
+            let Ok(Some(line)) = line else {
+                break;
+            };
+            history.push(&line);
+
+            // console messages are printed to stdout as well, they are read from console.log
+            if ConsoleEvent::parse(&line).is_none()
+                && let Some(record) = parser.push(&line)
+            {
+                sender.send(ServerEvent::from_record(record)).ok();
+            }
+        }
+
+        if let Some(record) = parser.flush() {
+            sender.send(ServerEvent::from_record(record)).ok();
+        }
+    }
+}
+
+impl Drop for FactorioTracker {
+    fn drop(&mut self) {
+        if let Some(handle) = self.handle.take() {
+            handle.abort();
+        }
+    }
+}
 
-        this
+#[cfg(test)]
+mod test {
+    use super::*;
+    use tokio::io::AsyncWriteExt;
+    use tokio::sync::broadcast;
+
+    #[tokio::test]
+    async fn entry_split_across_reads() {
+        let (mut writer, output) = tokio::io::duplex(1024);
+        let (sender, mut events) = broadcast::channel(16);
+        let reader = tokio::spawn(FactorioTracker::read_output(
+            output,
+            sender,
+            LogHistory::default(),
+        ));
+
+        writer
+            .write_all(b"   1.234 Error ModManager.cpp:123: Failed to load mods: __SomeMod__/data.lua:5: boom\n")
+            .await
+            .unwrap();
+        tokio::time::sleep(PENDING_TIMEOUT / 5).await;
+        writer
+            .write_all(b"\nMods to be disabled:\n \xe2\x80\xa2 OtherMod: Missing required dependency flib.\n")
+            .await
+            .unwrap();
+        drop(writer);
+        reader.await.unwrap();
+
+        let ServerEvent::ModLoadError(error) = events.recv().await.unwrap() else {
+            panic!("expected a mod load error");
+        };
+        let names: Vec<_> = error
+            .mods
+            .iter()
+            .map(|failure| failure.name.as_str())
+            .collect();
+        assert_eq!(names, ["SomeMod", "OtherMod"]);
+        assert!(events.try_recv().is_err());
     }
 }
@@ -1,30 +1,51 @@
 use crate::Progress;
+use crate::drop_guard::{self, AsyncDropGuard};
 use crate::error::ServerError;
-use crate::factorio_tracker::FactorioTracker;
+use crate::event::ServerEvent;
+use crate::factorio_tracker::{FactorioTracker, LogSource};
+use crate::lua;
 use crate::manager::Manager;
-use crate::utilities::{get_random_port, symlink_file, symlink_folder};
+use crate::map_settings::{MapGenSettings, MapSettings};
+use crate::mod_load_error::ModLoadError;
+use crate::process::{self, ProcessHandle};
+use crate::rcon_client::RconClient;
+use crate::server_settings::ServerSettings;
+use crate::supervisor::Supervisor;
+use crate::utilities::{get_free_port, symlink_file, symlink_folder};
 use crate::version::Version;
 use rand::Rng;
 use rand::distr::Alphanumeric;
-use rcon::Connection;
-use serde::Serialize;
-use std::net::{IpAddr, Ipv4Addr};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::fmt::{self, Formatter};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
 use std::path::{Path, PathBuf};
 use std::process::Stdio;
-use std::time::Duration;
-use sysinfo::{Pid, System};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::{Duration, SystemTime};
+use sysinfo::Pid;
+use thiserror::Error;
 use tokio::fs::{File, create_dir_all, remove_dir_all};
 use tokio::io::AsyncWriteExt;
-use tokio::net::TcpStream;
-use tokio::process::{Child, Command};
+use tokio::process::{ChildStdin, Command};
+use tokio::sync::Mutex;
+use tokio::sync::broadcast;
 use tokio::sync::broadcast::channel;
-use tokio::sync::watch::Sender;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::watch::{self, Sender};
 use tokio::task::JoinHandle;
-use tokio::time::timeout;
+use tokio::time::{sleep, timeout};
 
 const PID_FILE_NAME: &str = "factorio.pid";
-
-#[derive(PartialEq, Default, Debug)]
+const RUNTIME_FILE_NAME: &str = "runtime.json";
+const SERVER_SETTINGS_FILE_NAME: &str = "server-settings.json";
+const EVENT_CHANNEL_CAPACITY: usize = 256;
+const DEFAULT_START_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+const DEFAULT_STOP_TIMEOUT: Duration = Duration::from_secs(30);
+const DEFAULT_RCON_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug, Serialize, Deserialize)]
 pub enum Status {
     #[default]
     Stopped,
@@ -34,29 +55,228 @@ pub enum Status {
     Closed, // Set between factorio output "changing state from(Disconnected) to(Closed)" and process end.
 }
 
-pub struct Instance<'a> {
+/// The current status and when it was entered.
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub struct StatusUpdate {
+    pub status: Status,
+    pub changed_at: SystemTime,
+}
+
+impl StatusUpdate {
+    fn new(status: Status) -> Self {
+        Self {
+            status,
+            changed_at: SystemTime::now(),
+        }
+    }
+}
+
+/// Publish a new status, subscribers are only notified about actual transitions.
+pub(crate) fn set_status(sender: &Sender<StatusUpdate>, status: Status) {
+    sender.send_if_modified(|update| {
+        if update.status == status {
+            return false;
+        }
+        *update = StatusUpdate::new(status);
+        true
+    });
+}
+
+/// Why a server didn't reach `Status::Running`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum StartFailure {
+    /// The process exited while starting.
+    Exited,
+    /// The server didn't finish loading in time and was killed.
+    Timeout,
+    /// Factorio couldn't load the configured mods.
+    ModLoad(ModLoadError),
+}
+
+impl fmt::Display for StartFailure {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Exited => write!(f, "process exited"),
+            Self::Timeout => write!(f, "timed out"),
+            Self::ModLoad(error) => write!(f, "failed to load mods: {}", error),
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+#[error("{reason}, exit code: {exit_code:?}")]
+pub struct StartError {
+    pub reason: StartFailure,
+    /// Only known for processes spawned by us.
+    pub exit_code: Option<i32>,
+    /// The last lines of the factorio log, they usually contain the reason.
+    pub last_log_lines: Vec<String>,
+}
+
+pub struct Instance {
     settings: InstanceSettings,
 
     path: PathBuf,
     name: String,
 
-    manager: &'a Manager,
+    manager: Manager,
+}
+
+/// The process of a server, replaced when it is restarted after a crash.
+pub(crate) type SharedRuntime = Arc<std::sync::Mutex<Arc<ProcessRuntime>>>;
+
+/// Handle to a started server, clones refer to the same server.
+#[derive(Clone)]
+pub struct RunningInstance {
+    inner: Arc<RunningInstanceInner>,
 }
 
-pub struct RunningInstance<'a> {
+pub(crate) struct RunningInstanceInner {
     settings: InstanceSettings,
 
     path: PathBuf,
     name: String,
 
-    manager: &'a Manager,
+    manager: Manager,
+
+    /// Replaced by the supervisor when the server is restarted after a crash.
+    runtime: SharedRuntime,
+    rcon: Option<Arc<RconClient>>,
+    status: Sender<StatusUpdate>,
+    events: broadcast::Sender<ServerEvent>,
+    /// The last mod loading error, kept to explain a failed start.
+    mod_load_error: Arc<std::sync::Mutex<Option<ModLoadError>>>,
+    crash_count: Arc<AtomicU32>,
+    tracker_resv: Arc<JoinHandle<Result<(), ServerError>>>,
+    /// Applies the `DropPolicy` once all handles are dropped, disarmed by an explicit stop or kill.
+    drop_guard: std::sync::Mutex<Option<AsyncDropGuard>>,
+}
 
-    process: Child,
-    status: Sender<Status>,
+/// Everything that belongs to one factorio process, replaced when the server is restarted.
+pub(crate) struct ProcessRuntime {
+    pub(crate) process: ProcessHandle,
+    stdin: Option<Mutex<ChildStdin>>,
     tracker: FactorioTracker,
-    tracker_resv: JoinHandle<Result<(), ServerError>>,
 }
 
+impl ProcessRuntime {
+    /// Start factorio and follow its log, the rcon port has to be resolved already.
+    pub(crate) async fn spawn(
+        settings: &InstanceSettings,
+        path: &Path,
+        events: broadcast::Sender<ServerEvent>,
+    ) -> Result<Self, ServerError> {
+        let exec_path = path.join(&settings.executable_path);
+
+        let mut command = Command::new(exec_path);
+        command
+            .current_dir(path)
+            .args([
+                "--executable-path",
+                settings.executable_path.to_str().unwrap(),
+            ])
+            .args(settings.start_args(path)?)
+            .args([
+                "--console-log",
+                "console.log",
+                "--server-settings",
+                SERVER_SETTINGS_FILE_NAME,
+                "--no-log-rotation",
+                "--bind",
+                settings.host.to_string().as_str(),
+                "--port",
+                settings.port.to_string().as_str(),
+                "--mod-directory",
+                path.join("mods").to_str().unwrap(),
+            ])
+            // keep running if the managing process exits, it can be adopted again
+            .kill_on_drop(false);
+
+        if settings.command_transport.uses_rcon() {
+            command.args([
+                "--rcon-bind",
+                SocketAddr::new(settings.rcon_host, settings.rcon_port)
+                    .to_string()
+                    .as_str(),
+                "--rcon-password",
+                settings.rcon_pass.as_str(),
+            ]);
+        }
+
+        if settings.command_transport.uses_stdin() {
+            command.stdin(Stdio::piped());
+        } else {
+            command.stdin(Stdio::null());
+        }
+
+        if settings.capture_output {
+            command.stdout(Stdio::piped()).stderr(Stdio::piped());
+        } else {
+            command.stdout(Stdio::null()).stderr(Stdio::null());
+        }
+
+        let mut child = command.spawn()?;
+        let stdin = child.stdin.take().map(Mutex::new);
+        let log_source = match (child.stdout.take(), child.stderr.take()) {
+            (Some(stdout), Some(stderr)) => LogSource::Output { stdout, stderr },
+            _ => LogSource::File(path.join("factorio-current.log")),
+        };
+        let process = ProcessHandle::from_child(child)?;
+
+        // save pid
+        let pid_path = path.join(PID_FILE_NAME);
+        let mut pid_file = File::create(pid_path).await?;
+        pid_file
+            .write_all(process.pid().to_string().as_bytes())
+            .await?;
+
+        let state = RuntimeState {
+            pid: process.pid(),
+            settings: settings.clone(),
+        };
+        tokio::fs::write(path.join(RUNTIME_FILE_NAME), serde_json::to_vec(&state)?).await?;
+
+        let tracker = FactorioTracker::watch(
+            log_source,
+            path.join("console.log"),
+            process.subscribe(),
+            events,
+        );
+
+        Ok(Self {
+            process,
+            stdin,
+            tracker,
+        })
+    }
+
+    /// Follow a server started by an earlier run of the managing process.
+    fn adopt(pid: u32, path: &Path, events: broadcast::Sender<ServerEvent>) -> Self {
+        let process = ProcessHandle::from_pid(pid);
+        let tracker = FactorioTracker::watch(
+            LogSource::Resume(path.join("factorio-current.log")),
+            path.join("console.log"),
+            process.subscribe(),
+            events,
+        );
+
+        Self {
+            process,
+            stdin: None,
+            tracker,
+        }
+    }
+}
+
+/// Everything needed to adopt a server that was started by an earlier run of the managing process.
+#[derive(Clone, Serialize, Deserialize)]
+struct RuntimeState {
+    pid: u32,
+    /// The settings the server was started with, the rcon port is already resolved.
+    settings: InstanceSettings,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
 pub struct BaseMods {
     pub base: bool, // always has to be enabled
     pub elevated_rails: bool,
@@ -74,17 +294,138 @@ impl Default for BaseMods {
     }
 }
 
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Mod {
     name: String,
     version: Version,
 }
 
+/// How console commands are sent to the server.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum CommandTransport {
+    #[default]
+    Rcon,
+    /// Write commands to stdin of the process, works without RCON.
+    Stdin,
+    /// Use RCON, fall back to stdin if no RCON connection can be established.
+    /// Other RCON errors are returned, the command may have run already.
+    Both,
+}
+
+impl CommandTransport {
+    fn uses_rcon(self) -> bool {
+        matches!(self, Self::Rcon | Self::Both)
+    }
+
+    fn uses_stdin(self) -> bool {
+        matches!(self, Self::Stdin | Self::Both)
+    }
+}
+
+/// What the server loads when it starts.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum StartMode {
+    /// The save named like the saves folder, `<save>/<save>.zip`.
+    #[default]
+    Save,
+    /// The most recently written save in the saves folder, including autosaves.
+    LatestSave,
+    /// Start a fresh map from a scenario, e.g. `pvp` of the `base` mod.
+    /// Without a mod, the scenario is looked up in `data/scenarios`.
+    Scenario {
+        name: String,
+        mod_name: Option<String>,
+    },
+    /// Create the save on the first start, afterward it is loaded like `Save`.
+    /// Settings taken from a `MapExchangeString` only carry its typed parts.
+    NewMap {
+        map_gen_settings: MapGenSettings,
+        map_settings: MapSettings,
+        /// A random seed is used if this is `None`.
+        seed: Option<u32>,
+    },
+}
+
+/// What happens to a server when all handles to it are dropped.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DropPolicy {
+    /// Keep it running, it can be taken over again with `Manager::adopt_instance`.
+    #[default]
+    Detach,
+    /// Save the map and stop it, outside a tokio runtime it is killed instead.
+    Stop,
+    Kill,
+}
+
+/// How `RunningInstance::stop_graceful` shuts a server down.
+#[derive(Clone, Debug)]
+pub struct StopOptions {
+    /// Warn the players this long before the server stops, e.g. after 60, 30 and 10 seconds.
+    pub countdown: Vec<Duration>,
+    /// Chat message for the countdown, `{}` is replaced with the remaining seconds.
+    pub message: String,
+    /// Save the map before stopping.
+    pub save: bool,
+    pub save_timeout: Duration,
+}
+
+impl Default for StopOptions {
+    fn default() -> Self {
+        Self {
+            countdown: vec![
+                Duration::from_secs(60),
+                Duration::from_secs(30),
+                Duration::from_secs(10),
+            ],
+            message: "The server stops in {} seconds".to_string(),
+            save: true,
+            save_timeout: Duration::from_secs(60),
+        }
+    }
+}
+
+/// Restart the server if it crashes, waiting longer after each failed attempt.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RestartPolicy {
+    /// Give up after this many restarts in a row.
+    pub max_retries: u32,
+    /// Wait before the first restart, doubled for every further attempt.
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    /// A server that ran this long without crashing starts again with `initial_backoff`.
+    pub reset_after: Duration,
+}
+
+impl Default for RestartPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 5,
+            initial_backoff: Duration::from_secs(5),
+            max_backoff: Duration::from_secs(5 * 60),
+            reset_after: Duration::from_secs(60 * 60),
+        }
+    }
+}
+
+impl RestartPolicy {
+    /// How long to wait before the restart after `retries` failed ones.
+    pub(crate) fn backoff(&self, retries: u32) -> Duration {
+        self.initial_backoff
+            .saturating_mul(2u32.saturating_pow(retries))
+            .min(self.max_backoff)
+    }
+}
+
+/// Stored as `instance.json`, settings added later have defaults so older files still load.
+#[derive(Clone, Serialize, Deserialize)]
 pub struct InstanceSettings {
     pub executable_path: PathBuf,
     pub saves_path: PathBuf,
 
     pub factorio_version: Version,
     pub save: String, // Insert a save out of the `data` dir
+    #[serde(default)]
+    pub start_mode: StartMode,
 
     pub host: IpAddr,
     pub port: u16,
@@ -92,9 +433,49 @@ pub struct InstanceSettings {
     pub rcon_host: IpAddr,
     pub rcon_port: u16,
     pub rcon_pass: String,
+    /// How long connecting and each command may take, the connection is dropped after that.
+    #[serde(default = "default_rcon_timeout")]
+    pub rcon_timeout: Duration,
+
+    #[serde(default)]
+    pub command_transport: CommandTransport,
 
     pub mods: Vec<Mod>,
     pub base_mods: BaseMods,
+
+    #[serde(default)]
+    pub server_settings: ServerSettings,
+
+    /// Read the log from stdout/stderr instead of `factorio-current.log`.
+    /// Factorio can't outlive the managing process then, writing to the closed pipes kills it.
+    #[serde(default)]
+    pub capture_output: bool,
+
+    /// How long `Instance::start` waits for the map to be loaded.
+    #[serde(default = "default_start_timeout")]
+    pub start_timeout: Duration,
+    /// How long a stopping server gets to save, close and exit before it is killed.
+    #[serde(default = "default_stop_timeout")]
+    pub stop_timeout: Duration,
+    /// What happens to the server when its `RunningInstance` is dropped.
+    #[serde(default)]
+    pub drop_policy: DropPolicy,
+
+    /// Restart the server when it crashes, disabled by default.
+    #[serde(default)]
+    pub restart_policy: Option<RestartPolicy>,
+}
+
+fn default_rcon_timeout() -> Duration {
+    DEFAULT_RCON_TIMEOUT
+}
+
+fn default_start_timeout() -> Duration {
+    DEFAULT_START_TIMEOUT
+}
+
+fn default_stop_timeout() -> Duration {
+    DEFAULT_STOP_TIMEOUT
 }
 
 impl InstanceSettings {
@@ -107,20 +488,82 @@ impl InstanceSettings {
             saves_path: "saves".into(),
             factorio_version,
             save,
+            start_mode: StartMode::default(),
             host: default_addr,
             port: 34197u16,
-            rcon_host: default_addr,
+            // RCON gives full control over the server, don't expose it by default
+            rcon_host: IpAddr::V4(Ipv4Addr::LOCALHOST),
             rcon_port: 0u16,
             rcon_pass: rand::rng()
                 .sample_iter(&Alphanumeric)
                 .take(16)
                 .map(char::from)
                 .collect(),
+            rcon_timeout: DEFAULT_RCON_TIMEOUT,
+            command_transport: CommandTransport::default(),
             mods: vec![],
             base_mods: BaseMods::default(),
+            server_settings: ServerSettings::default(),
+            capture_output: false,
+            start_timeout: DEFAULT_START_TIMEOUT,
+            stop_timeout: DEFAULT_STOP_TIMEOUT,
+            drop_policy: DropPolicy::default(),
+            restart_policy: None,
+        })
+    }
+
+    /// Address to connect to RCON, the wildcard addresses are reached via loopback.
+    pub(crate) fn rcon_address(&self) -> SocketAddr {
+        let host = match self.rcon_host {
+            IpAddr::V4(host) if host.is_unspecified() => IpAddr::V4(Ipv4Addr::LOCALHOST),
+            IpAddr::V6(host) if host.is_unspecified() => IpAddr::V6(Ipv6Addr::LOCALHOST),
+            host => host,
+        };
+        SocketAddr::new(host, self.rcon_port)
+    }
+
+    /// Path of the save for `StartMode::Save` in the instance at `path`.
+    pub(crate) fn save_path(&self, path: &Path) -> PathBuf {
+        path.join(&self.saves_path)
+            .join(&self.save)
+            .with_extension("zip")
+    }
+
+    /// The arguments that tell factorio what to load.
+    pub(crate) fn start_args(&self, path: &Path) -> Result<Vec<String>, ServerError> {
+        Ok(match &self.start_mode {
+            StartMode::Save | StartMode::NewMap { .. } => vec![
+                "--start-server".to_string(),
+                self.save_path(path)
+                    .to_str()
+                    .ok_or(ServerError::Utf8Error())?
+                    .to_string(),
+            ],
+            StartMode::LatestSave => vec!["--start-server-load-latest".to_string()],
+            StartMode::Scenario { name, mod_name } => vec![
+                "--start-server-load-scenario".to_string(),
+                match mod_name {
+                    Some(mod_name) => format!("{}/{}", mod_name, name),
+                    None => name.clone(),
+                },
+            ],
         })
     }
 
+    /// Settings to restart a crashed server with.
+    /// Scenarios and new maps continue from their latest save instead of starting a fresh map.
+    pub(crate) fn restart_settings(&self, path: &Path) -> Self {
+        let mut settings = self.clone();
+        if matches!(
+            self.start_mode,
+            StartMode::Scenario { .. } | StartMode::NewMap { .. }
+        ) && has_saves(&path.join(&self.saves_path))
+        {
+            settings.start_mode = StartMode::LatestSave;
+        }
+        settings
+    }
+
     pub(crate) fn default_executable_path() -> PathBuf {
         #[cfg(target_os = "windows")]
         return "bin/x64/factorio.exe".into();
@@ -149,6 +592,11 @@ impl InstanceSettings {
         self
     }
 
+    pub fn start_mode(&mut self, start_mode: StartMode) -> &mut Self {
+        self.start_mode = start_mode;
+        self
+    }
+
     pub fn host(&mut self, host: IpAddr) -> &mut Self {
         self.host = host;
         self
@@ -174,6 +622,16 @@ impl InstanceSettings {
         self
     }
 
+    pub fn rcon_timeout(&mut self, rcon_timeout: Duration) -> &mut Self {
+        self.rcon_timeout = rcon_timeout;
+        self
+    }
+
+    pub fn command_transport(&mut self, command_transport: CommandTransport) -> &mut Self {
+        self.command_transport = command_transport;
+        self
+    }
+
     pub fn mods(&mut self, mods: Vec<Mod>) -> &mut Self {
         self.mods = mods;
         self
@@ -198,11 +656,41 @@ impl InstanceSettings {
         self.base_mods = base_mods;
         self
     }
+
+    pub fn server_settings(&mut self, server_settings: ServerSettings) -> &mut Self {
+        self.server_settings = server_settings;
+        self
+    }
+
+    pub fn capture_output(&mut self, capture_output: bool) -> &mut Self {
+        self.capture_output = capture_output;
+        self
+    }
+
+    pub fn start_timeout(&mut self, start_timeout: Duration) -> &mut Self {
+        self.start_timeout = start_timeout;
+        self
+    }
+
+    pub fn stop_timeout(&mut self, stop_timeout: Duration) -> &mut Self {
+        self.stop_timeout = stop_timeout;
+        self
+    }
+
+    pub fn drop_policy(&mut self, drop_policy: DropPolicy) -> &mut Self {
+        self.drop_policy = drop_policy;
+        self
+    }
+
+    pub fn restart_policy(&mut self, restart_policy: RestartPolicy) -> &mut Self {
+        self.restart_policy = Some(restart_policy);
+        self
+    }
 }
 
-impl<'a> Instance<'a> {
+impl Instance {
     pub(crate) async fn prepare(
-        manager: &'a Manager,
+        manager: &Manager,
         name: impl AsRef<str>,
         settings: InstanceSettings,
         instance_path: impl AsRef<Path>,
@@ -219,23 +707,11 @@ impl<'a> Instance<'a> {
             remove_dir_all(&instance_path).await?;
         }
 
-        let executable_path = instance_path.join(&settings.executable_path);
-        let executable_parent = executable_path.parent().ok_or(ServerError::NotAllowed(
-            "Configured executable path has no parent".to_string(),
-        ))?;
-        create_dir_all(&executable_parent).await?;
-
-        symlink_file(
-            factorio_cache_path.join(InstanceSettings::default_executable_path()),
-            executable_path,
-        )?;
-        symlink_file(
-            factorio_cache_path.join("config-path.cfg"),
-            instance_path.join("config-path.cfg"),
-        )?;
-        symlink_folder(factorio_cache_path.join("data"), instance_path.join("data"))?;
+        Self::link_factorio(&settings, instance_path, factorio_cache_path).await?;
 
         symlink_folder(saves_path, instance_path.join("saves"))?;
+        // scenarios without a mod are loaded from here
+        symlink_folder(manager.scenarios_folder(), instance_path.join("scenarios"))?;
 
         let mods_dir = instance_path.join("mods");
         create_dir_all(&mods_dir).await?;
@@ -258,6 +734,20 @@ impl<'a> Instance<'a> {
 
         build_mod_list_json(&settings, mods_dir.join("mod-list.json")).await?;
 
+        let credentials = if settings.server_settings.visibility.public {
+            Some(manager.cache().credentials().await?)
+        } else {
+            None
+        };
+        let server_settings = settings
+            .server_settings
+            .to_json(&settings.factorio_version, credentials.as_ref())?;
+        tokio::fs::write(
+            instance_path.join(SERVER_SETTINGS_FILE_NAME),
+            server_settings,
+        )
+        .await?;
+
         // copy in mod settings
         let mod_settings_dat = manager
             .load_backup_file(name.as_ref(), "mod-settings.dat")
@@ -277,10 +767,121 @@ impl<'a> Instance<'a> {
             settings,
             path: instance_path.into(),
             name: name.as_ref().to_string(),
-            manager,
+            manager: manager.clone(),
         })
     }
 
+    /// Link the factorio installation from the cache into `path`.
+    async fn link_factorio(
+        settings: &InstanceSettings,
+        path: &Path,
+        factorio_cache_path: &Path,
+    ) -> Result<(), ServerError> {
+        let executable_path = path.join(&settings.executable_path);
+        let executable_parent = executable_path.parent().ok_or(ServerError::NotAllowed(
+            "Configured executable path has no parent".to_string(),
+        ))?;
+        create_dir_all(&executable_parent).await?;
+
+        symlink_file(
+            factorio_cache_path.join(InstanceSettings::default_executable_path()),
+            executable_path,
+        )?;
+        symlink_file(
+            factorio_cache_path.join("config-path.cfg"),
+            path.join("config-path.cfg"),
+        )?;
+        symlink_folder(factorio_cache_path.join("data"), path.join("data"))?;
+
+        Ok(())
+    }
+
+    /// Create a new map at `save_path` with `factorio --create`, in a temporary installation at `path`.
+    /// Only the base mods of `settings` are enabled.
+    pub(crate) async fn create_save(
+        settings: &InstanceSettings,
+        path: &Path,
+        factorio_cache_path: &Path,
+        save_path: &Path,
+        map_gen_settings: &MapGenSettings,
+        map_settings: &MapSettings,
+        seed: Option<u32>,
+    ) -> Result<(), ServerError> {
+        let created = async {
+            Self::link_factorio(settings, path, factorio_cache_path).await?;
+
+            let mods_dir = path.join("mods");
+            create_dir_all(&mods_dir).await?;
+            build_mod_list_json(settings, mods_dir.join("mod-list.json")).await?;
+
+            Self::create_map(
+                settings,
+                path,
+                save_path,
+                map_gen_settings,
+                map_settings,
+                seed,
+            )
+            .await
+        }
+        .await;
+
+        // the directory is removed on failure as well, that error is the interesting one then
+        let removed = remove_dir_all(path).await;
+        created?;
+        Ok(removed?)
+    }
+
+    /// Run `factorio --create` in the prepared installation at `path`.
+    async fn create_map(
+        settings: &InstanceSettings,
+        path: &Path,
+        save_path: &Path,
+        map_gen_settings: &MapGenSettings,
+        map_settings: &MapSettings,
+        seed: Option<u32>,
+    ) -> Result<(), ServerError> {
+        tokio::fs::write(
+            path.join("map-gen-settings.json"),
+            map_gen_settings.to_json(&settings.factorio_version)?,
+        )
+        .await?;
+        tokio::fs::write(path.join("map-settings.json"), map_settings.to_json()?).await?;
+
+        let mut command = Command::new(path.join(&settings.executable_path));
+        command
+            .current_dir(path)
+            .args([
+                "--executable-path",
+                settings.executable_path.to_str().unwrap(),
+                "--create",
+                save_path.to_str().ok_or(ServerError::Utf8Error())?,
+                "--map-gen-settings",
+                "map-gen-settings.json",
+                "--map-settings",
+                "map-settings.json",
+                "--mod-directory",
+                path.join("mods").to_str().unwrap(),
+            ])
+            .stdin(Stdio::null());
+        if let Some(seed) = seed {
+            command.args(["--map-gen-seed", seed.to_string().as_str()]);
+        }
+
+        let output = command.output().await?;
+
+        if !output.status.success() || !save_path.exists() {
+            // factorio logs the reason to stdout
+            let stdout = String::from_utf8_lossy(&output.stdout);
+            let lines: Vec<_> = stdout.trim_end().lines().collect();
+            return Err(ServerError::CreateSaveFailed(
+                lines[lines.len().saturating_sub(10)..].join("\n"),
+            ));
+        }
+
+        Ok(())
+    }
+
     pub(crate) async fn check_running(instance_path: impl AsRef<Path>) -> Result<(), ServerError> {
         let instance_path = instance_path.as_ref();
         if !instance_path.exists() {
@@ -294,150 +895,438 @@ impl<'a> Instance<'a> {
 
         let pid = tokio::fs::read_to_string(&pid_file).await?;
         let pid = pid.parse::<Pid>()?;
-        let system = System::new_all();
-        let process = system.process(pid);
-        if process.is_some() {
+        if process::is_running(pid) {
             return Err(ServerError::AlreadyRunningError);
         }
 
         Ok(())
     }
 
-    pub async fn start(mut self) -> Result<RunningInstance<'a>, ServerError> {
-        let exec_path = self.path.join(&self.settings.executable_path);
+    /// Start the server and wait until the map is loaded.
+    /// If the server exits or doesn't get ready within `start_timeout`, it is killed and a `StartError` is returned.
+    pub async fn start(self) -> Result<RunningInstance, ServerError> {
+        let start_timeout = self.settings.start_timeout;
+        let instance = self.spawn().await?;
+
+        if let Err(err) = instance.wait_ready(start_timeout).await {
+            instance.runtime().process.kill().await.ok();
+            instance.cleanup().await?;
+            return Err(err);
+        }
+
+        Ok(instance)
+    }
+
+    /// Start the server without waiting for it, the status is `Starting` until the map is loaded.
+    pub async fn spawn(mut self) -> Result<RunningInstance, ServerError> {
+        if self.settings.command_transport.uses_rcon() {
+            self.settings.rcon_port =
+                get_free_port(self.settings.rcon_host, self.settings.rcon_port).await?;
+        }
 
-        let save_path = self
-            .path
-            .join(&self.settings.saves_path)
-            .join(&self.settings.save)
-            .with_extension("zip");
+        if let StartMode::NewMap {
+            map_gen_settings,
+            map_settings,
+            seed,
+        } = &self.settings.start_mode
+        {
+            let save_path = self.settings.save_path(&self.path);
+            if !save_path.exists() {
+                Self::create_map(
+                    &self.settings,
+                    &self.path,
+                    &save_path,
+                    map_gen_settings,
+                    map_settings,
+                    *seed,
+                )
+                .await?;
+                // the log of the server is followed from its start, it must not replay this run
+                let create_log = self.path.join("factorio-current.log");
+                if create_log.exists() {
+                    tokio::fs::rename(create_log, self.path.join("factorio-create.log")).await?;
+                }
+            }
+        }
 
-        let (sender, mut recv) = channel::<String>(32);
+        let (sender, recv) = channel::<ServerEvent>(EVENT_CHANNEL_CAPACITY);
+        let runtime = ProcessRuntime::spawn(&self.settings, &self.path, sender.clone()).await?;
 
-        let tracker = FactorioTracker::watch(
-            self.path.join("factorio-current.log"),
-            self.path.join(PID_FILE_NAME),
-            sender,
-        );
+        Ok(self.run(runtime, (sender, recv), Status::Starting))
+    }
 
-        self.settings.rcon_port = if self.settings.rcon_port != 0 {
-            self.settings.rcon_port
-        } else {
-            get_random_port(self.settings.rcon_host).await?
+    /// Take over a server that is still running from a previous run of the managing process.
+    /// Commands are sent via RCON with the persisted parameters, the log is followed from its current end.
+    pub(crate) async fn adopt(
+        manager: &Manager,
+        name: impl AsRef<str>,
+        instance_path: impl AsRef<Path>,
+    ) -> Result<RunningInstance, ServerError> {
+        let instance_path = instance_path.as_ref();
+        if manager.is_registered(name.as_ref()) {
+            return Err(ServerError::InstanceAlreadyRunning(
+                name.as_ref().to_string(),
+            ));
+        }
+
+        let state_path = instance_path.join(RUNTIME_FILE_NAME);
+        if !state_path.exists() {
+            return Err(ServerError::NotAllowed(
+                "Instance has no running server".to_string(),
+            ));
+        }
+        let state: RuntimeState =
+            serde_json::from_str(&tokio::fs::read_to_string(state_path).await?)?;
+
+        if !process::is_factorio(Pid::from_u32(state.pid)) {
+            return Err(ServerError::NotAllowed(
+                "Instance has no running server".to_string(),
+            ));
+        }
+        // stdin belonged to the previous managing process
+        if !state.settings.command_transport.uses_rcon() {
+            return Err(ServerError::NotAllowed(
+                "Only servers with RCON can be adopted".to_string(),
+            ));
+        }
+
+        let instance = Self {
+            settings: state.settings,
+            path: instance_path.into(),
+            name: name.as_ref().to_string(),
+            manager: manager.clone(),
         };
 
-        let mut command = Command::new(exec_path);
-        command
-            .current_dir(&self.path)
-            .args([
-                "--executable-path",
-                self.settings.executable_path.to_str().unwrap(),
-                "--start-server",
-                save_path.to_str().ok_or(ServerError::Utf8Error())?,
-                "--console-log",
-                "console.log",
-                "--no-log-rotation",
-                "--bind",
-                self.settings.host.to_string().as_str(),
-                "--port",
-                self.settings.port.to_string().as_str(),
-                "--rcon-bind",
-                format!("{}:{}", self.settings.rcon_host, self.settings.rcon_port).as_str(),
-                "--rcon-password",
-                self.settings.rcon_pass.as_str(),
-                "--mod-directory",
-                self.path.join("mods").to_str().unwrap(),
-            ])
-            .stdout(Stdio::null())
-            .stderr(Stdio::null())
-            .stdin(Stdio::null())
-            .kill_on_drop(true);
+        let (sender, recv) = channel::<ServerEvent>(EVENT_CHANNEL_CAPACITY);
+        let runtime = ProcessRuntime::adopt(state.pid, &instance.path, sender.clone());
 
-        let process = command.spawn()?;
+        // there is no way to find out the current state, it's likely still running
+        Ok(instance.run(runtime, (sender, recv), Status::Running))
+    }
 
-        // save pid
-        let pid = process
-            .id()
-            .ok_or(ServerError::NotAllowed("Process has no pid".into()))?;
-        let pid_path = self.path.join(PID_FILE_NAME);
-        let mut pid_file = File::create(pid_path).await?;
-        pid_file.write_all(pid.to_string().as_bytes()).await?;
+    /// Supervise the started process.
+    fn run(
+        self,
+        runtime: ProcessRuntime,
+        (sender, recv): (
+            broadcast::Sender<ServerEvent>,
+            broadcast::Receiver<ServerEvent>,
+        ),
+        status: Status,
+    ) -> RunningInstance {
+        let runtime = Arc::new(std::sync::Mutex::new(Arc::new(runtime)));
+
+        let rcon = self.settings.command_transport.uses_rcon().then(|| {
+            Arc::new(RconClient::new(
+                self.settings.rcon_address(),
+                &self.settings.rcon_pass,
+                self.settings.rcon_timeout,
+            ))
+        });
 
-        let (status_sender, _) = tokio::sync::watch::channel(Default::default());
+        let (status_sender, _) = tokio::sync::watch::channel(StatusUpdate::new(status));
+        let mod_load_error = Arc::new(std::sync::Mutex::new(None));
+        let crash_count = Arc::new(AtomicU32::new(0));
+
+        let supervisor = Supervisor {
+            settings: self.settings.clone(),
+            path: self.path.clone(),
+            name: self.name.clone(),
+            manager: self.manager.clone(),
+            events: sender.clone(),
+            runtime: runtime.clone(),
+            rcon: rcon.clone(),
+            status: status_sender.clone(),
+            mod_load_error: mod_load_error.clone(),
+            crash_count: crash_count.clone(),
+        };
+        let tracker_resv = Arc::new(tokio::spawn(supervisor.run(recv)));
+
+        let instance = RunningInstance {
+            inner: Arc::new(RunningInstanceInner {
+                path: self.path,
+                settings: self.settings,
+                runtime,
+                rcon,
+                status: status_sender,
+                events: sender,
+                mod_load_error,
+                crash_count,
+                tracker_resv,
+                drop_guard: std::sync::Mutex::new(None),
+                manager: self.manager.clone(),
+                name: self.name,
+            }),
+        };
 
-        let status_sender2 = status_sender.clone();
+        if instance.inner.settings.drop_policy != DropPolicy::Detach {
+            let detached = instance.detached();
+            let fallback = instance.detached();
+            *instance.inner.drop_guard.lock().unwrap() = Some(drop_guard::new_async(
+                async move {
+                    if let Err(err) = detached.apply_drop_policy().await {
+                        detached
+                            .inner
+                            .events
+                            .send(ServerEvent::DropPolicyFailed {
+                                error: err.to_string(),
+                            })
+                            .ok();
+                    }
+                },
+                move || fallback.kill_sync(),
+            ));
+        }
 
-        let tracker_resv = tokio::spawn(async move {
-            loop {
-                let line = recv.recv().await?;
+        // the manager's handle doesn't apply the drop policy, it tracks the server until it exits
+        self.manager.register_instance(instance.detached());
+        instance
+    }
+}
 
-                println!("{}", line);
+impl RunningInstance {
+    /// Subscribe to the events of this server.
+    /// Only events that happen after subscribing are received.
+    pub fn subscribe(&self) -> broadcast::Receiver<ServerEvent> {
+        self.inner.events.subscribe()
+    }
 
-                if line == "factorio process stopped" {
-                    status_sender.send_replace(Status::Stopped);
-                    break;
-                }
+    pub fn name(&self) -> &str {
+        &self.inner.name
+    }
 
-                if line.ends_with("changing state from(CreatingGame) to(InGame)") {
-                    println!("State changed to Running");
-                    status_sender.send_replace(Status::Running);
-                }
+    pub fn status(&self) -> Status {
+        self.inner.status.borrow().status
+    }
 
-                if line.ends_with("changing state from(Disconnected) to(Closed)") {
-                    status_sender.send_replace(Status::Closed);
-                }
-            }
+    /// When the current status was entered.
+    pub fn status_changed_at(&self) -> SystemTime {
+        self.inner.status.borrow().changed_at
+    }
 
-            Ok(())
-        });
+    /// Watch the status, e.g. to wait for a transition:
+    /// `instance.subscribe_status().wait_for(|update| update.status == Status::Running).await`.
+    pub fn subscribe_status(&self) -> watch::Receiver<StatusUpdate> {
+        self.inner.status.subscribe()
+    }
 
-        command.kill_on_drop(false);
+    /// How often the server crashed and was restarted by its `RestartPolicy`.
+    pub fn crash_count(&self) -> u32 {
+        self.inner.crash_count.load(Ordering::Relaxed)
+    }
 
-        Ok(RunningInstance {
-            path: self.path,
-            settings: self.settings,
-            process,
-            status: status_sender2,
-            tracker,
-            tracker_resv,
-            manager: self.manager,
-            name: self.name,
-        })
+    /// The current process, it changes when the server is restarted.
+    fn runtime(&self) -> Arc<ProcessRuntime> {
+        self.inner.runtime.lock().unwrap().clone()
     }
-}
 
-impl<'a> RunningInstance<'a> {
-    pub async fn kill(&mut self) -> Result<(), ServerError> {
-        self.check_and_set_status(Status::Running, Status::Stopping)
-            .await?;
+    /// Wait until the map is loaded.
+    /// Fails if the server stops before that or doesn't get ready within `timeout`.
+    pub async fn wait_ready(&self, timeout_after: Duration) -> Result<(), ServerError> {
+        let mut status = self.inner.status.subscribe();
+        // don't keep the borrow of the status, it would block its updates
+        let ready = timeout(
+            timeout_after,
+            status.wait_for(|update| update.status != Status::Starting),
+        )
+        .await
+        .map(|update| update.is_ok_and(|update| update.status == Status::Running));
+
+        let reason = match ready {
+            Ok(true) => return Ok(()),
+            Ok(false) => StartFailure::Exited,
+            Err(_) => StartFailure::Timeout,
+        };
+
+        let exit_code = match reason {
+            StartFailure::Exited => self.runtime().process.wait().await?,
+            _ => None,
+        };
+        // the process exit is reported after everything it logged, so a mod error is known by now
+        let reason = match (reason, self.inner.mod_load_error.lock().unwrap().take()) {
+            (StartFailure::Exited, Some(error)) => StartFailure::ModLoad(error),
+            (reason, _) => reason,
+        };
 
-        self.process.kill().await?;
-        self.process.wait().await?; // wait for the process to finish before dropping the Child as recommended by lib
+        Err(StartError {
+            reason,
+            exit_code,
+            last_log_lines: self.runtime().tracker.last_lines(),
+        }
+        .into())
+    }
+
+    pub async fn kill(&self) -> Result<(), ServerError> {
+        // a server that is still loading or waiting to be restarted can be killed as well
+        if self.status() == Status::Starting {
+            set_status(&self.inner.status, Status::Stopping);
+        } else {
+            self.check_and_set_status(Status::Running, Status::Stopping)
+                .await?;
+        }
+        self.disarm_drop_guard();
+
+        self.runtime().process.kill().await?;
 
         self.cleanup().await?;
 
         Ok(())
     }
 
-    pub async fn stop(&mut self) -> Result<(), ServerError> {
+    pub async fn stop(&self) -> Result<(), ServerError> {
         self.check_and_set_status(Status::Running, Status::Stopping)
             .await?;
+        self.disarm_drop_guard();
 
-        // send /quit via rcon
-        self.send_command_internal("/quit").await?;
+        self.quit().await
+    }
 
-        // wait for either
-        // - process.wait
-        // - status_recv.wait_for + 3s
-        let mut status = self.status.subscribe();
-        let _ = status.wait_for(|val| *val == Status::Closed).await?;
+    /// Warn the players, save the map and stop the server.
+    /// Fails without stopping if the save doesn't finish within `save_timeout`.
+    pub async fn stop_graceful(&self, options: &StopOptions) -> Result<(), ServerError> {
+        self.check_status(Status::Running).await?;
 
-        if timeout(Duration::from_secs(3), self.process.wait())
-            .await
-            .is_err()
+        let mut countdown = options.countdown.clone();
+        countdown.sort_unstable_by(|a, b| b.cmp(a));
+        for (i, remaining) in countdown.iter().enumerate() {
+            // a chat message is everything that isn't a command, it has to stay on one line
+            let message = options
+                .message
+                .replace("{}", &remaining.as_secs().to_string())
+                .replace('\n', " ");
+            self.send_command(message.trim_start_matches('/')).await?;
+
+            let next = countdown.get(i + 1).copied().unwrap_or_default();
+            sleep(*remaining - next).await;
+        }
+
+        if options.save {
+            // subscribe first, the save could finish before the command returns
+            let mut events = self.subscribe();
+            self.server_save().await?;
+
+            let finished = timeout(options.save_timeout, async {
+                loop {
+                    match events.recv().await {
+                        Ok(ServerEvent::SaveFinished) => return Ok(()),
+                        Ok(_) | Err(RecvError::Lagged(_)) => continue,
+                        Err(err) => return Err(err),
+                    }
+                }
+            })
+            .await;
+            match finished {
+                Ok(finished) => finished?,
+                Err(_) => {
+                    return Err(ServerError::CommandFailed(
+                        "Saving didn't finish in time".to_string(),
+                    ));
+                }
+            }
+        }
+
+        self.stop().await
+    }
+
+    /// Save and stop without warning the players, a server that is still starting is killed.
+    pub(crate) async fn save_and_stop(&self) -> Result<(), ServerError> {
+        match self.status() {
+            Status::Running => {
+                let options = StopOptions {
+                    countdown: vec![],
+                    ..Default::default()
+                };
+                let stopped = self.stop_graceful(&options).await;
+                // stop anyway, a failed save shouldn't keep the server running
+                if stopped.is_err() && self.status() == Status::Running {
+                    self.stop().await?;
+                }
+                stopped
+            }
+            Status::Starting => self.kill().await,
+            // already stopping
+            _ => Ok(()),
+        }
+    }
+
+    /// Apply the `DropPolicy` now instead of when the last handle is dropped.
+    ///
+    /// Dropping outside a tokio runtime kills the server, because it can't be saved without one,
+    /// and while the runtime shuts down the policy isn't applied at all.
+    /// Use this to be sure the server is stopped and to see if that failed.
+    pub async fn close(self) -> Result<(), ServerError> {
+        self.disarm_drop_guard();
+        self.detached().apply_drop_policy().await
+    }
+
+    /// Runs once all handles to the server are dropped.
+    async fn apply_drop_policy(&self) -> Result<(), ServerError> {
+        match self.inner.settings.drop_policy {
+            DropPolicy::Detach => Ok(()),
+            DropPolicy::Stop => self.save_and_stop().await,
+            DropPolicy::Kill => match self.status() {
+                Status::Running | Status::Starting => self.kill().await,
+                _ => Ok(()),
+            },
+        }
+    }
+
+    /// Kill the process without a runtime, when the `DropPolicy` can't be applied otherwise.
+    fn kill_sync(&self) {
+        if self.inner.settings.drop_policy == DropPolicy::Detach
+            || !matches!(self.status(), Status::Running | Status::Starting)
         {
-            self.process.kill().await.ok();
-            self.process.wait().await.ok();
+            return;
+        }
+        let pid = Pid::from_u32(self.runtime().process.pid());
+        // the pid could have been reused if factorio exited in the meantime
+        if process::is_factorio(pid) {
+            process::kill(pid);
+        }
+    }
+
+    /// Another handle to the same server that doesn't apply the `DropPolicy`.
+    fn detached(&self) -> Self {
+        let inner = &self.inner;
+        Self {
+            inner: Arc::new(RunningInstanceInner {
+                settings: inner.settings.clone(),
+                path: inner.path.clone(),
+                name: inner.name.clone(),
+                manager: inner.manager.clone(),
+                runtime: inner.runtime.clone(),
+                rcon: inner.rcon.clone(),
+                status: inner.status.clone(),
+                events: inner.events.clone(),
+                mod_load_error: inner.mod_load_error.clone(),
+                crash_count: inner.crash_count.clone(),
+                tracker_resv: inner.tracker_resv.clone(),
+                drop_guard: std::sync::Mutex::new(None),
+            }),
+        }
+    }
+
+    fn disarm_drop_guard(&self) {
+        if let Some(guard) = self.inner.drop_guard.lock().unwrap().take() {
+            guard.disarm();
+        }
+    }
+
+    async fn quit(&self) -> Result<(), ServerError> {
+        self.send_command_internal("/quit").await?;
+
+        // a server that hangs while closing or never logs it is killed after stop_timeout
+        let mut status = self.inner.status.subscribe();
+        let runtime = self.runtime();
+        let stopped = timeout(self.inner.settings.stop_timeout, async {
+            status
+                .wait_for(|val| matches!(val.status, Status::Closed | Status::Stopped))
+                .await?;
+            runtime.process.wait().await
+        })
+        .await;
+        if !matches!(stopped, Ok(Ok(_))) {
+            runtime.process.kill().await.ok();
         }
 
         self.cleanup().await?;
@@ -445,22 +1334,50 @@ impl<'a> RunningInstance<'a> {
         Ok(())
     }
 
-    async fn send_command_internal(&self, command: &str) -> Result<(), ServerError> {
-        let mut connection = <Connection<TcpStream>>::builder()
-            .enable_factorio_quirks(true)
-            // TODO: think if that should be the actual ip (if not 0.0.0.0)
-            .connect(
-                format!("{}:{}", "127.0.0.1", self.settings.rcon_port),
-                self.settings.rcon_pass.as_str(),
-            )
-            .await?;
+    async fn send_command_internal(&self, command: &str) -> Result<String, ServerError> {
+        match self.inner.settings.command_transport {
+            CommandTransport::Rcon => self.send_rcon(command).await,
+            CommandTransport::Stdin => self.send_stdin(command).await,
+            CommandTransport::Both => match self.send_rcon(command).await {
+                Err(ServerError::RconUnavailable(_)) => self.send_stdin(command).await,
+                result => result,
+            },
+        }
+    }
 
-        connection.cmd(command).await?;
+    /// There is no way to get the response via stdin, an empty string is returned.
+    async fn send_stdin(&self, command: &str) -> Result<String, ServerError> {
+        let runtime = self.runtime();
+        let stdin = runtime.stdin.as_ref().ok_or(ServerError::NotAllowed(
+            "stdin of the factorio process is not available".to_string(),
+        ))?;
+        // every line is a separate command
+        if command.contains('\n') {
+            return Err(ServerError::NotAllowed(
+                "commands sent via stdin can't contain newlines".to_string(),
+            ));
+        }
 
-        Ok(())
+        let mut stdin = stdin.lock().await;
+        stdin.write_all(command.as_bytes()).await?;
+        stdin.write_all(b"\n").await?;
+        stdin.flush().await?;
+
+        Ok(String::new())
+    }
+
+    async fn send_rcon(&self, command: &str) -> Result<String, ServerError> {
+        self.inner
+            .rcon
+            .as_ref()
+            .ok_or(ServerError::NotAllowed("RCON is disabled".to_string()))?
+            .cmd(command)
+            .await
     }
 
-    pub async fn send_command(&self, command: &str) -> Result<(), ServerError> {
+    /// Send a console command and return its response.
+    /// Commands sent via stdin have no response, the returned string is empty.
+    pub async fn send_command(&self, command: &str) -> Result<String, ServerError> {
         // TODO: this could fail (race-condition), cause:
         // 1. check_status(Running) -> succeeds
         // 2. kill()
@@ -471,13 +1388,30 @@ impl<'a> RunningInstance<'a> {
         self.send_command_internal(command).await
     }
 
+    /// Send a command via RCON and return the response, fails if RCON is disabled.
+    pub(crate) async fn query(&self, command: &str) -> Result<String, ServerError> {
+        self.check_status(Status::Running).await?;
+
+        self.send_rcon(command).await
+    }
+
+    /// Run a lua snippet on the server and decode its return value.
+    /// `code` is used as function body, so the result has to be returned: `return game.tick`.
+    /// Values are converted with `table_to_json`, so only json-compatible values can be returned.
+    pub async fn eval_lua<T: DeserializeOwned>(&self, code: &str) -> Result<T, ServerError> {
+        // rcon.print only writes to RCON, stdin can't be used here
+        let response = self
+            .query(&lua::wrap(code, &self.inner.settings.factorio_version))
+            .await?;
+        lua::decode(&response)
+    }
+
     async fn check_status(&self, expected: Status) -> Result<(), ServerError> {
-        let mut status = self.status.subscribe();
-        let status = status.borrow_and_update();
-        if *status != expected {
+        let status = self.status();
+        if status != expected {
             return Err(ServerError::NotAllowed(format!(
                 "Status not as expected {:?} != {:?}",
-                *status, expected
+                status, expected
             )));
         }
 
@@ -485,39 +1419,55 @@ impl<'a> RunningInstance<'a> {
     }
 
     async fn check_and_set_status(
-        &mut self,
+        &self,
         expected: Status,
         new_status: Status,
     ) -> Result<(), ServerError> {
-        {
-            let mut status = self.status.subscribe();
-            let status = status.borrow_and_update();
-            if *status != expected {
-                return Err(ServerError::NotAllowed(format!(
-                    "Status (with set) not as expected {:?} != {:?}",
-                    *status, expected
-                )));
-            }
+        let status = self.status();
+        if status != expected {
+            return Err(ServerError::NotAllowed(format!(
+                "Status (with set) not as expected {:?} != {:?}",
+                status, expected
+            )));
         }
-        self.status.send_replace(new_status);
+        set_status(&self.inner.status, new_status);
 
         Ok(())
     }
 
     async fn cleanup(&self) -> Result<(), ServerError> {
-        self.manager
+        if let Some(rcon) = &self.inner.rcon {
+            rcon.disconnect().await;
+        }
+
+        self.inner
+            .manager
             .backup_files(
-                &self.name,
+                &self.inner.name,
                 vec![
-                    self.path.join("factorio-current.log"),
-                    self.path.join("console.log"),
-                    self.path.join("mods").join("mod-settings.dat"),
-                    self.path.join("mods").join("mod-settings.json"),
+                    self.inner.path.join("factorio-current.log"),
+                    self.inner.path.join("console.log"),
+                    self.inner.path.join("mods").join("mod-settings.dat"),
+                    self.inner.path.join("mods").join("mod-settings.json"),
                 ],
             )
             .await?;
         Ok(())
     }
+
+    /// Whether this handle refers to the server with this runtime.
+    pub(crate) fn has_runtime(&self, runtime: &SharedRuntime) -> bool {
+        Arc::ptr_eq(&self.inner.runtime, runtime)
+    }
+}
+
+/// Whether the folder contains a save, e.g. an autosave.
+fn has_saves(dir: &Path) -> bool {
+    std::fs::read_dir(dir).is_ok_and(|mut entries| {
+        entries.any(|entry| {
+            entry.is_ok_and(|entry| entry.path().extension().is_some_and(|ext| ext == "zip"))
+        })
+    })
 }
 
 #[derive(Serialize)]
@@ -610,3 +1560,115 @@ async fn build_mod_list_json(
 //         return "/mnt/c/Data/Development/GO/factorio";
 //     }
 // }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::Value;
+
+    fn settings(start_mode: StartMode) -> InstanceSettings {
+        let mut settings =
+            InstanceSettings::new("world".to_string(), Version::from([2, 0, 28])).unwrap();
+        settings.start_mode(start_mode);
+        settings
+    }
+
+    #[test]
+    fn start_args() {
+        let path = Path::new("instance");
+        assert_eq!(
+            settings(StartMode::Save).start_args(path).unwrap(),
+            vec![
+                "--start-server".to_string(),
+                Path::new("instance/saves/world.zip")
+                    .to_str()
+                    .unwrap()
+                    .to_string()
+            ]
+        );
+        assert_eq!(
+            settings(StartMode::LatestSave).start_args(path).unwrap(),
+            vec!["--start-server-load-latest"]
+        );
+        assert_eq!(
+            settings(StartMode::Scenario {
+                name: "pvp".to_string(),
+                mod_name: Some("base".to_string()),
+            })
+            .start_args(path)
+            .unwrap(),
+            vec!["--start-server-load-scenario", "base/pvp"]
+        );
+        assert_eq!(
+            settings(StartMode::Scenario {
+                name: "event".to_string(),
+                mod_name: None,
+            })
+            .start_args(path)
+            .unwrap(),
+            vec!["--start-server-load-scenario", "event"]
+        );
+    }
+
+    #[test]
+    fn restart_from_latest_save() {
+        let path = std::env::temp_dir().join(format!("restart_settings_{}", std::process::id()));
+        std::fs::create_dir_all(path.join("saves")).unwrap();
+        let scenario = settings(StartMode::Scenario {
+            name: "pvp".to_string(),
+            mod_name: Some("base".to_string()),
+        });
+
+        // nothing was saved yet, the scenario has to be started again
+        assert_eq!(
+            scenario.restart_settings(&path).start_mode,
+            scenario.start_mode
+        );
+
+        std::fs::write(path.join("saves").join("_autosave1.zip"), []).unwrap();
+        assert_eq!(
+            scenario.restart_settings(&path).start_mode,
+            StartMode::LatestSave
+        );
+        assert_eq!(
+            settings(StartMode::Save).restart_settings(&path).start_mode,
+            StartMode::Save
+        );
+
+        std::fs::remove_dir_all(path).unwrap();
+    }
+
+    #[test]
+    fn load_older_settings() {
+        let mut json = serde_json::to_value(settings(StartMode::LatestSave)).unwrap();
+        // the fields instance.json had before they became configurable
+        let fields = json.as_object_mut().unwrap();
+        fields.retain(|name, _| {
+            [
+                "executable_path",
+                "saves_path",
+                "factorio_version",
+                "save",
+                "host",
+                "port",
+                "rcon_host",
+                "rcon_port",
+                "rcon_pass",
+                "mods",
+                "base_mods",
+            ]
+            .contains(&name.as_str())
+        });
+
+        let loaded: InstanceSettings =
+            serde_json::from_value(Value::Object(fields.clone())).unwrap();
+        assert_eq!(loaded.save, "world");
+        assert_eq!(loaded.start_mode, StartMode::Save);
+        assert_eq!(loaded.command_transport, CommandTransport::Rcon);
+        assert_eq!(loaded.server_settings, ServerSettings::default());
+        assert_eq!(loaded.stop_timeout, DEFAULT_STOP_TIMEOUT);
+        assert_eq!(loaded.rcon_timeout, DEFAULT_RCON_TIMEOUT);
+        assert_eq!(loaded.drop_policy, DropPolicy::Detach);
+        assert!(loaded.restart_policy.is_none());
+    }
+}
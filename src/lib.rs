@@ -1,12 +1,24 @@
+pub mod admin;
 pub mod cache;
+pub mod console_log;
 pub(crate) mod credentials;
-mod data;
+pub mod data;
 pub(crate) mod drop_guard;
 mod error;
+pub mod event;
 mod factorio_tracker;
 pub mod instance;
+pub mod log_parser;
+mod lua;
 pub mod manager;
+pub mod map_exchange;
+pub mod map_settings;
+pub mod mod_load_error;
 pub mod mod_portal;
+mod process;
+mod rcon_client;
+pub mod server_settings;
+mod supervisor;
 pub(crate) mod utilities;
 pub mod version;
 
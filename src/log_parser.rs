@@ -0,0 +1,270 @@
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use std::time::Duration;
+
+/// Severity of a log entry. Factorio omits it for plain informational lines like `Loading mod ...`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum LogLevel {
+    Verbose,
+    Debug,
+    Info,
+    Warning,
+    Error,
+    /// Output of `log()` calls from mods and scenarios.
+    Script,
+}
+
+impl FromStr for LogLevel {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "Verbose" => Ok(Self::Verbose),
+            "Debug" => Ok(Self::Debug),
+            "Info" => Ok(Self::Info),
+            "Warning" => Ok(Self::Warning),
+            "Error" => Ok(Self::Error),
+            "Script" => Ok(Self::Script),
+            _ => Err(()),
+        }
+    }
+}
+
+impl Display for LogLevel {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Self::Verbose => "Verbose",
+            Self::Debug => "Debug",
+            Self::Info => "Info",
+            Self::Warning => "Warning",
+            Self::Error => "Error",
+            Self::Script => "Script",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Where a log entry was emitted, e.g. `ServerMultiplayerManager.cpp:808` or `@__level__/control.lua:12`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct SourceLocation {
+    pub file: String,
+    pub line: u32,
+}
+
+/// A single entry of `factorio-current.log`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LogRecord {
+    /// Time since factorio started. `None` for lines that carry no timestamp,
+    /// e.g. console messages or output written before logging was set up.
+    pub uptime: Option<Duration>,
+    pub level: Option<LogLevel>,
+    pub source: Option<SourceLocation>,
+    /// The message, continuation lines (stack traces, mod errors, ...) are joined with `\n`.
+    pub message: String,
+}
+
+impl LogRecord {
+    /// Parse the first line of an entry, returns `None` if the line has no uptime prefix.
+    fn parse_header(line: &str) -> Option<Self> {
+        let line = line.trim_start();
+        let (uptime, rest) = line.split_once(' ').unwrap_or((line, ""));
+        let (secs, millis) = uptime.split_once('.')?;
+        if secs.is_empty()
+            || millis.len() != 3
+            || !secs.bytes().all(|b| b.is_ascii_digit())
+            || !millis.bytes().all(|b| b.is_ascii_digit())
+        {
+            return None;
+        }
+        let uptime =
+            Duration::from_millis(secs.parse::<u64>().ok()? * 1000 + millis.parse::<u64>().ok()?);
+
+        let (level, rest) = match rest.split_once(' ') {
+            Some((level, message)) => match level.parse::<LogLevel>() {
+                Ok(level) => (Some(level), message),
+                Err(_) => (None, rest),
+            },
+            None => (None, rest),
+        };
+
+        let (source, message) = if level.is_some() {
+            Self::parse_source(rest)
+        } else {
+            (None, rest)
+        };
+
+        Some(Self {
+            uptime: Some(uptime),
+            level,
+            source,
+            message: message.to_string(),
+        })
+    }
+
+    fn parse_source(rest: &str) -> (Option<SourceLocation>, &str) {
+        let Some((location, message)) = rest.split_once(": ") else {
+            // entries without message, e.g. "Error Foo.cpp:12:"
+            return match rest.strip_suffix(':').and_then(Self::parse_location) {
+                Some(source) => (Some(source), ""),
+                None => (None, rest),
+            };
+        };
+
+        match Self::parse_location(location) {
+            Some(source) => (Some(source), message),
+            None => (None, rest),
+        }
+    }
+
+    fn parse_location(location: &str) -> Option<SourceLocation> {
+        if location.contains(' ') {
+            return None;
+        }
+        let (file, line) = location.rsplit_once(':')?;
+        Some(SourceLocation {
+            file: file.to_string(),
+            line: line.parse().ok()?,
+        })
+    }
+
+    /// Console messages (`2024-05-01 12:00:00 [CHAT] ...`) are interleaved with the log on stdout,
+    /// they never continue the previous entry.
+    fn is_console_line(line: &str) -> bool {
+        let bytes = line.as_bytes();
+        bytes.len() > 20
+            && bytes[4] == b'-'
+            && bytes[7] == b'-'
+            && bytes[10] == b' '
+            && bytes[13] == b':'
+            && bytes[16] == b':'
+            && bytes[19] == b' '
+            && bytes[..4].iter().all(u8::is_ascii_digit)
+    }
+}
+
+/// Turns log lines into `LogRecord`s. Lines without a timestamp are appended to the previous entry.
+#[derive(Default)]
+pub struct LogParser {
+    pending: Option<LogRecord>,
+}
+
+impl LogParser {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Feed a single line (without line ending).
+    /// Returns the previous entry once it is known to be complete.
+    pub fn push(&mut self, line: &str) -> Option<LogRecord> {
+        let header = LogRecord::parse_header(line);
+        if header.is_some() || LogRecord::is_console_line(line) {
+            let finished = self.flush();
+            self.pending = Some(header.unwrap_or_else(|| LogRecord {
+                uptime: None,
+                level: None,
+                source: None,
+                message: line.to_string(),
+            }));
+            return finished;
+        }
+
+        match &mut self.pending {
+            Some(record) => {
+                record.message.push('\n');
+                record.message.push_str(line);
+                None
+            }
+            None => {
+                self.pending = Some(LogRecord {
+                    uptime: None,
+                    level: None,
+                    source: None,
+                    message: line.to_string(),
+                });
+                None
+            }
+        }
+    }
+
+    /// Whether an entry is waiting for more lines or the next header.
+    pub fn has_pending(&self) -> bool {
+        self.pending.is_some()
+    }
+
+    /// Return the pending entry, call this when no more lines will follow it,
+    /// e.g. because nothing was logged for a while or the process exited.
+    pub fn flush(&mut self) -> Option<LogRecord> {
+        let mut record = self.pending.take()?;
+        let trimmed_len = record.message.trim_end().len();
+        record.message.truncate(trimmed_len);
+        Some(record)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn header() {
+        let mut parser = LogParser::new();
+        assert_eq!(
+            parser.push(
+                "   2.345 Info ServerMultiplayerManager.cpp:808: updateTick(4294967295) changing state from(CreatingGame) to(InGame)"
+            ),
+            None
+        );
+        assert_eq!(
+            parser.flush(),
+            Some(LogRecord {
+                uptime: Some(Duration::from_millis(2345)),
+                level: Some(LogLevel::Info),
+                source: Some(SourceLocation {
+                    file: "ServerMultiplayerManager.cpp".to_string(),
+                    line: 808,
+                }),
+                message: "updateTick(4294967295) changing state from(CreatingGame) to(InGame)"
+                    .to_string(),
+            })
+        );
+
+        parser.push("   0.694 Loading mod core 0.0.0 (data.lua)");
+        let record = parser.flush().unwrap();
+        assert_eq!(record.level, None);
+        assert_eq!(record.source, None);
+        assert_eq!(record.message, "Loading mod core 0.0.0 (data.lua)");
+
+        parser.push("  12.000 Script @__level__/control.lua:10: hello");
+        let record = parser.flush().unwrap();
+        assert_eq!(record.level, Some(LogLevel::Script));
+        assert_eq!(record.source.unwrap().file, "@__level__/control.lua");
+        assert_eq!(record.message, "hello");
+    }
+
+    #[test]
+    fn multi_line() {
+        let mut parser = LogParser::new();
+        parser.push("   1.083 Error Util.cpp:83: Failed to load mods: Error while loading");
+        parser.push("Modifications: Base mod › Foo");
+        parser.push("");
+        parser.push("Mods to be disabled:");
+        parser.push("• Foo");
+        let record = parser.push("   1.100 Info Foo.cpp:1: next").unwrap();
+        assert_eq!(
+            record.message,
+            "Failed to load mods: Error while loading\nModifications: Base mod › Foo\n\nMods to be disabled:\n• Foo"
+        );
+        assert_eq!(record.uptime, Some(Duration::from_millis(1083)));
+
+        let record = parser
+            .push("2024-05-01 12:00:00 [JOIN] some_player joined the game")
+            .unwrap();
+        assert_eq!(record.message, "next");
+        let record = parser.flush().unwrap();
+        assert_eq!(record.uptime, None);
+        assert_eq!(
+            record.message,
+            "2024-05-01 12:00:00 [JOIN] some_player joined the game"
+        );
+    }
+}
@@ -0,0 +1,89 @@
+use crate::error::ServerError;
+use crate::version::Version;
+use serde::Deserialize;
+use serde::de::DeserializeOwned;
+
+#[derive(Deserialize)]
+struct LuaResult {
+    ok: bool,
+    // `nil` values are missing in the json
+    #[serde(default)]
+    value: serde_json::Value,
+}
+
+/// Build a command that runs `code` as function body and prints its return value as json.
+pub(crate) fn wrap(code: &str, factorio_version: &Version) -> String {
+    // table_to_json moved from `game` to `helpers` with 2.0
+    let table_to_json = if factorio_version >= &Version::from([2, 0, 0]) {
+        "helpers.table_to_json"
+    } else {
+        "game.table_to_json"
+    };
+
+    format!(
+        "/silent-command local ok, value = pcall(function()\n{}\nend) \
+         if not ok then value = tostring(value) end \
+         rcon.print({}({{ok = ok, value = value}}))",
+        code, table_to_json
+    )
+}
+
+/// Decode the output of a command built with `wrap`.
+pub(crate) fn decode<T: DeserializeOwned>(response: &str) -> Result<T, ServerError> {
+    // the snippet could print something itself, our result is always the last line
+    let line = response.trim_end().lines().last().unwrap_or_default();
+    let result: LuaResult = serde_json::from_str(line).map_err(|_| {
+        // factorio prints errors (e.g. syntax errors) instead of running the command
+        ServerError::LuaError(response.trim().to_string())
+    })?;
+
+    if !result.ok {
+        return Err(ServerError::LuaError(match result.value {
+            serde_json::Value::String(message) => message,
+            value => value.to_string(),
+        }));
+    }
+
+    Ok(serde_json::from_value(result.value)?)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn wrap_version() {
+        assert!(wrap("return 1", &Version::from([1, 1, 110])).contains("game.table_to_json"));
+        assert!(wrap("return 1", &Version::from([2, 0, 28])).contains("helpers.table_to_json"));
+    }
+
+    #[test]
+    fn decode_values() {
+        let value: HashMap<String, u32> =
+            decode("{\"ok\":true,\"value\":{\"iron-plate\":12}}").unwrap();
+        assert_eq!(value["iron-plate"], 12);
+
+        let value: Option<u32> = decode("{\"ok\":true}").unwrap();
+        assert_eq!(value, None);
+
+        let value: u32 = decode("printed by the snippet\n{\"ok\":true,\"value\":5}\n").unwrap();
+        assert_eq!(value, 5);
+    }
+
+    #[test]
+    fn decode_errors() {
+        let err = decode::<u32>("{\"ok\":false,\"value\":\"[string]:1: attempt to index nil\"}")
+            .unwrap_err();
+        assert!(
+            matches!(err, ServerError::LuaError(message) if message == "[string]:1: attempt to index nil")
+        );
+
+        let err = decode::<u32>("Cannot execute command. Error: [string \"...\"]:2: '=' expected")
+            .unwrap_err();
+        assert!(matches!(err, ServerError::LuaError(_)));
+
+        let err = decode::<u32>("{\"ok\":true,\"value\":\"text\"}").unwrap_err();
+        assert!(matches!(err, ServerError::SerdeJsonError(_)));
+    }
+}
@@ -1,19 +1,37 @@
 use crate::Progress;
 use crate::cache::Cache;
-use crate::data::Data;
+use crate::data::{Data, SaveInfo};
 use crate::error::ServerError;
-use crate::instance::{Instance, InstanceSettings};
+use crate::instance::{
+    Instance, InstanceSettings, RunningInstance, SharedRuntime, StartMode, Status,
+};
+use crate::map_settings::{MapGenSettings, MapSettings};
 use crate::utilities::assure_subdir;
 use crate::version::Version;
+use futures::StreamExt;
+use rand::Rng;
+use rand::distr::Alphanumeric;
+use std::collections::HashMap;
 use std::fs::create_dir_all;
 use std::path::{Path, PathBuf};
-use tokio::fs::rename;
+use std::sync::{Arc, Mutex};
+use tokio::fs::{remove_dir, remove_dir_all};
 
+const INSTANCE_FILE_NAME: &str = "instance.json";
+
+/// Cheap to clone, all clones share the same cache and data.
+#[derive(Clone)]
 pub struct Manager {
+    inner: Arc<ManagerInner>,
+}
+
+struct ManagerInner {
     root_path: PathBuf,
     cache: Cache,
     data: Data,
     instances_path: PathBuf,
+    /// Started servers, they are removed once their process exits.
+    running: Mutex<HashMap<String, RunningInstance>>,
 }
 
 impl Manager {
@@ -25,35 +43,49 @@ impl Manager {
         assure_subdir(&instances_path)?;
 
         Ok(Self {
-            root_path: root_path.clone(),
-            cache: Cache::new(root_path.join("cache"))?,
-            data: Data::new(root_path.join("data"))?,
-            instances_path,
+            inner: Arc::new(ManagerInner {
+                root_path: root_path.clone(),
+                cache: Cache::new(root_path.join("cache"))?,
+                data: Data::new(root_path.join("data"))?,
+                instances_path,
+                running: Mutex::new(HashMap::new()),
+            }),
         })
     }
 
     pub fn cache(&self) -> &Cache {
-        &self.cache
+        &self.inner.cache
     }
 
     /// prepare a new instance, will download and await factorio and all needed mods.
+    /// The stored definition of the instance isn't changed, see `update_instance`.
     pub async fn prepare_instance(
         &self,
         name: String,
         settings: InstanceSettings,
         progress: &mut Progress,
-    ) -> Result<Instance<'_>, ServerError> {
-        let instance_path = self.instances_path.join(&name);
+    ) -> Result<Instance, ServerError> {
+        check_instance_name(&name)?;
+        let instance_path = self.inner.instances_path.join(&name);
 
         Instance::check_running(&instance_path).await?;
 
         let mut sub_prog = progress.allocate_fraction((settings.mods.len() + 1) as u64);
         let factorio_cache_path = self
+            .inner
             .cache
             .get_factorio(&settings.factorio_version, &mut sub_prog)
             .await?;
 
-        let saves_path = self.data.get_saves_folder(&settings.save)?;
+        // scenarios and new maps start without a save
+        let saves_path = match settings.start_mode {
+            StartMode::Save | StartMode::LatestSave => {
+                self.inner.data.get_saves_folder(&settings.save)?
+            }
+            StartMode::Scenario { .. } | StartMode::NewMap { .. } => {
+                self.inner.data.create_saves_folder(&settings.save).await?
+            }
+        };
 
         Instance::prepare(
             self,
@@ -67,25 +99,382 @@ impl Manager {
         .await
     }
 
+    /// Prepare a defined instance with its stored settings.
+    pub async fn prepare_defined_instance(
+        &self,
+        name: impl AsRef<str>,
+        progress: &mut Progress,
+    ) -> Result<Instance, ServerError> {
+        let settings = self.get_instance_settings(name.as_ref()).await?;
+        self.prepare_instance(name.as_ref().to_string(), settings, progress)
+            .await
+    }
+
+    /// Prepare and start a defined instance with its stored settings.
+    pub async fn start_instance(
+        &self,
+        name: impl AsRef<str>,
+        progress: &mut Progress,
+    ) -> Result<RunningInstance, ServerError> {
+        self.prepare_defined_instance(name, progress)
+            .await?
+            .start()
+            .await
+    }
+
+    /// Names of all defined instances.
+    pub async fn list_instances(&self) -> Result<Vec<String>, ServerError> {
+        Ok(self
+            .inner
+            .data
+            .list_instances_with(INSTANCE_FILE_NAME)
+            .await?)
+    }
+
+    pub async fn get_instance_settings(
+        &self,
+        name: impl AsRef<str>,
+    ) -> Result<InstanceSettings, ServerError> {
+        let path = self.instance_file(name.as_ref())?;
+        if !path.exists() {
+            return Err(ServerError::InstanceNotFound(name.as_ref().to_string()));
+        }
+
+        Ok(serde_json::from_str(
+            &tokio::fs::read_to_string(path).await?,
+        )?)
+    }
+
+    /// Define a new instance, fails if one with this name exists already.
+    pub async fn create_instance(
+        &self,
+        name: impl AsRef<str>,
+        settings: &InstanceSettings,
+    ) -> Result<(), ServerError> {
+        if self.instance_file(name.as_ref())?.exists() {
+            return Err(ServerError::NotAllowed(format!(
+                "Instance {} already exists",
+                name.as_ref()
+            )));
+        }
+        self.save_instance_settings(name, settings).await
+    }
+
+    /// Replace the settings of an instance, they are used the next time it is prepared.
+    pub async fn update_instance(
+        &self,
+        name: impl AsRef<str>,
+        settings: &InstanceSettings,
+    ) -> Result<(), ServerError> {
+        if !self.instance_file(name.as_ref())?.exists() {
+            return Err(ServerError::InstanceNotFound(name.as_ref().to_string()));
+        }
+        self.save_instance_settings(name, settings).await
+    }
+
+    /// Delete the definition and everything stored for the instance, including backed up logs and mod settings.
+    pub async fn delete_instance(&self, name: impl AsRef<str>) -> Result<(), ServerError> {
+        let name = name.as_ref();
+        check_instance_name(name)?;
+
+        let instance_path = self.inner.instances_path.join(name);
+        Instance::check_running(&instance_path).await?;
+        if instance_path.exists() {
+            remove_dir_all(&instance_path).await?;
+        }
+
+        Ok(self.inner.data.remove_files(name).await?)
+    }
+
+    fn instance_file(&self, name: &str) -> Result<PathBuf, ServerError> {
+        check_instance_name(name)?;
+        Ok(self.inner.data.get_file_path(name, INSTANCE_FILE_NAME))
+    }
+
+    async fn save_instance_settings(
+        &self,
+        name: impl AsRef<str>,
+        settings: &InstanceSettings,
+    ) -> Result<(), ServerError> {
+        check_instance_name(name.as_ref())?;
+        let path = self
+            .inner
+            .data
+            .get_file(name.as_ref(), INSTANCE_FILE_NAME)
+            .await?;
+        tokio::fs::write(path, serde_json::to_vec_pretty(settings)?).await?;
+        Ok(())
+    }
+
+    /// Take over a server that is still running, e.g. after the managing process was restarted.
+    /// Fails with `InstanceAlreadyRunning` if this manager already runs or adopted it.
+    pub async fn adopt_instance(
+        &self,
+        name: impl AsRef<str>,
+    ) -> Result<RunningInstance, ServerError> {
+        check_instance_name(name.as_ref())?;
+        let instance_path = self.inner.instances_path.join(name.as_ref());
+        Instance::adopt(self, name, instance_path).await
+    }
+
+    /// All servers started or adopted by this manager that haven't stopped yet, sorted by name.
+    /// This includes servers whose handles were all dropped with `DropPolicy::Detach`.
+    /// Dropping the returned handles doesn't apply the `DropPolicy`.
+    pub fn running_instances(&self) -> Vec<RunningInstance> {
+        let mut instances: Vec<_> = self
+            .inner
+            .running
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|instance| instance.status() != Status::Stopped)
+            .cloned()
+            .collect();
+        instances.sort_by(|a, b| a.name().cmp(b.name()));
+        instances
+    }
+
+    /// Stop all running servers, at most `concurrency` at the same time.
+    pub async fn stop_all(&self, concurrency: usize) -> Result<(), ServerError> {
+        let instances = self
+            .running_instances()
+            .into_iter()
+            .filter(|instance| instance.status() == Status::Running)
+            .collect();
+        run_all(instances, concurrency, |instance| async move {
+            instance.stop().await
+        })
+        .await
+    }
+
+    /// Kill all running and starting servers, at most `concurrency` at the same time.
+    pub async fn kill_all(&self, concurrency: usize) -> Result<(), ServerError> {
+        let instances = self
+            .running_instances()
+            .into_iter()
+            .filter(|instance| matches!(instance.status(), Status::Running | Status::Starting))
+            .collect();
+        run_all(instances, concurrency, |instance| async move {
+            instance.kill().await
+        })
+        .await
+    }
+
+    /// Save and stop all servers in parallel, e.g. before the host reboots.
+    /// Servers that are still starting are killed.
+    pub async fn shutdown(&self) -> Result<(), ServerError> {
+        run_all(
+            self.running_instances(),
+            usize::MAX,
+            |instance| async move { instance.save_and_stop().await },
+        )
+        .await
+    }
+
+    /// Whether a server with this name was started or adopted and hasn't stopped yet.
+    pub(crate) fn is_registered(&self, name: &str) -> bool {
+        self.inner
+            .running
+            .lock()
+            .unwrap()
+            .get(name)
+            .is_some_and(|instance| instance.status() != Status::Stopped)
+    }
+
+    pub(crate) fn register_instance(&self, instance: RunningInstance) {
+        self.inner
+            .running
+            .lock()
+            .unwrap()
+            .insert(instance.name().to_string(), instance);
+    }
+
+    /// Called once the process of a server exited, a newer server with the same name is kept.
+    pub(crate) fn unregister_instance(&self, name: &str, runtime: &SharedRuntime) {
+        let mut running = self.inner.running.lock().unwrap();
+        if running
+            .get(name)
+            .is_some_and(|instance| instance.has_runtime(runtime))
+        {
+            running.remove(name);
+        }
+    }
+
+    /// Scenarios in `data/scenarios` can be started with `StartMode::Scenario` without a mod.
+    pub(crate) fn scenarios_folder(&self) -> &Path {
+        self.inner.data.get_scenarios_folder()
+    }
+
     pub(crate) async fn backup_files(
         &self,
         instance_name: impl AsRef<str>,
         paths: Vec<impl AsRef<Path>>,
     ) -> Result<(), ServerError> {
-        for path in paths {
-            let path = path.as_ref();
-            if path.exists() {
-                if let Some(filename) = path.file_name() {
-                    let rotated_log = self
-                        .data
-                        .get_and_rotate_file(instance_name.as_ref(), filename.to_str().unwrap(), 9)
-                        .await?;
-                    rename(path, &rotated_log).await?;
-                }
-            }
+        self.inner.data.backup_files(instance_name, paths).await
+    }
+
+    /// Generate a new map with factorio `version`, it is stored as `data/saves/<name>/<name>.zip`.
+    /// Without a `seed` a random one is used. Returns the path of the save.
+    pub async fn create_save(
+        &self,
+        name: impl AsRef<str>,
+        version: &Version,
+        map_gen_settings: &MapGenSettings,
+        map_settings: &MapSettings,
+        seed: Option<u32>,
+        progress: &mut Progress,
+    ) -> Result<PathBuf, ServerError> {
+        let name = name.as_ref();
+        check_save_name(name)?;
+
+        let save_path = self.inner.data.save_file(name, name);
+        if save_path.exists() {
+            return Err(ServerError::NotAllowed(format!(
+                "Save {} already exists",
+                name
+            )));
         }
 
-        Ok(())
+        let factorio_cache_path = self.inner.cache.get_factorio(version, progress).await?;
+        let settings = InstanceSettings::new(name.to_string(), *version)?;
+
+        // only created now, a folder left over from a failed attempt would show up in the library
+        let new_folder = !save_path.parent().is_some_and(Path::exists);
+        let save_folder = self.inner.data.create_saves_folder(name).await?;
+
+        // every call gets its own directory, so saves can be created concurrently
+        let suffix: String = rand::rng()
+            .sample_iter(&Alphanumeric)
+            .take(8)
+            .map(char::from)
+            .collect();
+        let created = Instance::create_save(
+            &settings,
+            &self
+                .inner
+                .root_path
+                .join("tmp")
+                .join(format!("{}-{}", name, suffix)),
+            &factorio_cache_path,
+            &save_path,
+            map_gen_settings,
+            map_settings,
+            seed,
+        )
+        .await;
+        if created.is_err() && new_folder {
+            // fails if factorio left anything behind, that is kept then
+            remove_dir(save_folder).await.ok();
+        }
+        created?;
+
+        Ok(save_path)
+    }
+
+    /// Folders in `data/saves`, each of them can be used as `InstanceSettings::save`.
+    pub async fn list_save_folders(&self) -> Result<Vec<String>, ServerError> {
+        Ok(self.inner.data.list_save_folders().await?)
+    }
+
+    /// The saves of a folder, the newest first.
+    pub async fn list_saves(&self, folder: impl AsRef<str>) -> Result<Vec<SaveInfo>, ServerError> {
+        check_save_name(folder.as_ref())?;
+        self.inner.data.list_saves(folder.as_ref()).await
+    }
+
+    /// Add a save to the library as `data/saves/<folder>/<name>.zip`.
+    /// Fails if `source` isn't a factorio save or the save exists already.
+    pub async fn import_save(
+        &self,
+        folder: impl AsRef<str>,
+        name: impl AsRef<str>,
+        source: impl AsRef<Path>,
+    ) -> Result<PathBuf, ServerError> {
+        check_save_name(folder.as_ref())?;
+        check_save_name(name.as_ref())?;
+        self.inner
+            .data
+            .import_save(folder.as_ref(), name.as_ref(), source.as_ref())
+            .await
+    }
+
+    pub async fn export_save(
+        &self,
+        folder: impl AsRef<str>,
+        name: impl AsRef<str>,
+        destination: impl AsRef<Path>,
+    ) -> Result<(), ServerError> {
+        check_save_name(folder.as_ref())?;
+        check_save_name(name.as_ref())?;
+        self.inner
+            .data
+            .export_save(folder.as_ref(), name.as_ref(), destination.as_ref())
+            .await
+    }
+
+    pub async fn delete_save(
+        &self,
+        folder: impl AsRef<str>,
+        name: impl AsRef<str>,
+    ) -> Result<(), ServerError> {
+        check_save_name(folder.as_ref())?;
+        check_save_name(name.as_ref())?;
+        self.inner
+            .data
+            .delete_save(folder.as_ref(), name.as_ref())
+            .await
+    }
+
+    pub async fn rename_save(
+        &self,
+        folder: impl AsRef<str>,
+        name: impl AsRef<str>,
+        new_name: impl AsRef<str>,
+    ) -> Result<(), ServerError> {
+        check_save_name(folder.as_ref())?;
+        check_save_name(name.as_ref())?;
+        check_save_name(new_name.as_ref())?;
+        self.inner
+            .data
+            .rename_save(folder.as_ref(), name.as_ref(), new_name.as_ref())
+            .await
+    }
+
+    /// Copy a save into another folder, e.g. to start a second instance from it.
+    pub async fn copy_save(
+        &self,
+        folder: impl AsRef<str>,
+        name: impl AsRef<str>,
+        to_folder: impl AsRef<str>,
+        new_name: impl AsRef<str>,
+    ) -> Result<PathBuf, ServerError> {
+        for name in [
+            folder.as_ref(),
+            name.as_ref(),
+            to_folder.as_ref(),
+            new_name.as_ref(),
+        ] {
+            check_save_name(name)?;
+        }
+        self.inner
+            .data
+            .copy_save(
+                folder.as_ref(),
+                name.as_ref(),
+                to_folder.as_ref(),
+                new_name.as_ref(),
+            )
+            .await
+    }
+
+    /// Delete all but the `keep` newest autosaves of a folder, returns the deleted ones.
+    pub async fn prune_autosaves(
+        &self,
+        folder: impl AsRef<str>,
+        keep: usize,
+    ) -> Result<Vec<SaveInfo>, ServerError> {
+        check_save_name(folder.as_ref())?;
+        self.inner.data.prune_autosaves(folder.as_ref(), keep).await
     }
 
     pub(crate) async fn load_backup_file(
@@ -94,6 +483,7 @@ impl Manager {
         name: impl AsRef<str>,
     ) -> Result<PathBuf, ServerError> {
         Ok(self
+            .inner
             .data
             .get_file(instance_name.as_ref(), name.as_ref())
             .await?)
@@ -105,7 +495,7 @@ impl Manager {
         version: &Version,
         prog: &mut Progress,
     ) -> Result<PathBuf, ServerError> {
-        self.cache.get_mod(name, version, prog).await
+        self.inner.cache.get_mod(name, version, prog).await
     }
 
     pub async fn get_factorio(
@@ -113,14 +503,51 @@ impl Manager {
         version: &Version,
         progress: &mut Progress,
     ) -> Result<PathBuf, ServerError> {
-        self.cache.get_factorio(version, progress).await
+        self.inner.cache.get_factorio(version, progress).await
     }
 }
 
+/// Run `f` for all instances, at most `concurrency` at the same time.
+/// All instances are handled even if some fail, the first error is returned.
+async fn run_all<F, Fut>(
+    instances: Vec<RunningInstance>,
+    concurrency: usize,
+    f: F,
+) -> Result<(), ServerError>
+where
+    F: Fn(RunningInstance) -> Fut,
+    Fut: Future<Output = Result<(), ServerError>>,
+{
+    let results: Vec<_> = futures::stream::iter(instances.into_iter().map(f))
+        .buffer_unordered(concurrency.max(1))
+        .collect()
+        .await;
+    results.into_iter().collect()
+}
+
+/// Instance and save names are used as directory names.
+fn is_valid_name(name: &str) -> bool {
+    !name.is_empty() && name != "." && name != ".." && !name.contains(['/', '\\', ':', '\0'])
+}
+
+fn check_instance_name(name: &str) -> Result<(), ServerError> {
+    if !is_valid_name(name) {
+        return Err(ServerError::InvalidInstanceName(name.to_string()));
+    }
+    Ok(())
+}
+
+fn check_save_name(name: &str) -> Result<(), ServerError> {
+    if !is_valid_name(name) {
+        return Err(ServerError::InvalidSaveName(name.to_string()));
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod test {
-    use crate::instance::InstanceSettings;
-    use crate::manager::Manager;
+    use crate::instance::{Instance, InstanceSettings, RunningInstance};
+    use crate::manager::{Manager, check_instance_name};
     use crate::version::Version;
     use prognest::Progress;
     use std::time::Duration;
@@ -129,13 +556,13 @@ mod test {
     #[tokio::test]
     async fn test() {
         #[cfg(target_os = "linux")]
-        let mut manager = Manager::new("/mnt/c/Data/Development/tmp/factorio-server-root").unwrap();
+        let manager = Manager::new("/mnt/c/Data/Development/tmp/factorio-server-root").unwrap();
         #[cfg(target_os = "windows")]
-        let mut manager =
+        let manager =
             Manager::new("C:\\Data\\Development\\tmp\\factorio-server-root-windows").unwrap();
 
         manager
-            .cache
+            .cache()
             .factorio_login(
                 dotenvy::var("factorio_username").unwrap(),
                 dotenvy::var("factorio_password").unwrap(),
@@ -154,10 +581,26 @@ mod test {
             .prepare_instance("test_1.1.110".to_string(), settings, &mut progress)
             .await
             .unwrap();
-        let mut instance = instance.start().await.unwrap();
+        let instance = instance.start().await.unwrap();
 
         sleep(Duration::from_secs(5)).await;
 
         instance.stop().await.unwrap();
     }
+
+    #[test]
+    fn instance_names() {
+        assert!(check_instance_name("test_1.1.110").is_ok());
+        assert!(check_instance_name("").is_err());
+        assert!(check_instance_name("..").is_err());
+        assert!(check_instance_name("../other").is_err());
+    }
+
+    #[test]
+    fn handles_are_send_sync() {
+        fn assert_send_sync<T: Send + Sync + 'static>() {}
+        assert_send_sync::<Manager>();
+        assert_send_sync::<Instance>();
+        assert_send_sync::<RunningInstance>();
+    }
 }
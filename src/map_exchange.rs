@@ -0,0 +1,674 @@
+use crate::error::ServerError;
+use crate::map_settings::{AutoplaceSettings, CliffSettings, MapGenSettings, MapSettings};
+use crate::version::Version;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use flate2::Compression;
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use std::io::{Read, Write};
+use std::str::FromStr;
+
+const PREFIX: &str = ">>>";
+const SUFFIX: &str = "<<<";
+
+/// Offsets of the typed fields in the binary map settings, everything in between is kept as decoded.
+/// Taken from the documented 1.1 format, they haven't been checked against strings exported by 2.0,
+/// which added settings to the map gen settings (e.g. territory) and possibly to the map settings.
+mod offset {
+    pub(super) const POLLUTION_ENABLED: usize = 0;
+    pub(super) const DIFFUSION_RATIO: usize = 1;
+    pub(super) const MIN_TO_DIFFUSE: usize = 9;
+    pub(super) const AGEING: usize = 17;
+    pub(super) const MIN_POLLUTION_TO_DAMAGE_TREES: usize = 41;
+    pub(super) const POLLUTION_RESTORED_PER_TREE_DAMAGE: usize = 65;
+    pub(super) const ENEMY_ATTACK_POLLUTION_CONSUMPTION_MODIFIER: usize = 81;
+    // followed by the steering settings
+    pub(super) const EVOLUTION_ENABLED: usize = 139;
+    pub(super) const TIME_FACTOR: usize = 140;
+    pub(super) const DESTROY_FACTOR: usize = 148;
+    pub(super) const POLLUTION_FACTOR: usize = 156;
+    pub(super) const EXPANSION_ENABLED: usize = 164;
+    pub(super) const MAX_EXPANSION_DISTANCE: usize = 165;
+    pub(super) const SETTLER_GROUP_MIN_SIZE: usize = 217;
+    pub(super) const SETTLER_GROUP_MAX_SIZE: usize = 221;
+    pub(super) const MIN_EXPANSION_COOLDOWN: usize = 225;
+    pub(super) const MAX_EXPANSION_COOLDOWN: usize = 229;
+    pub(super) const END: usize = 233;
+}
+
+/// A map exchange string as shared by players, `>>>eNp...<<<`.
+///
+/// The string is a zlib compressed, base64 encoded binary with the factorio version,
+/// the map gen settings, the map settings and a CRC32 checksum.
+/// Only the settings the crate has types for are decoded,
+/// everything else is kept as is, so `encode` only changes what was changed on the typed settings.
+///
+/// The untyped parts, e.g. autoplace settings, starting points, property expressions
+/// and map settings without a type, only survive `decode` and `encode`.
+/// A `StartMode::NewMap` built from `map_gen_settings` and `map_settings`
+/// uses factorio's defaults for them.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MapExchangeString {
+    /// Factorio version that created the string.
+    pub version: Version,
+    pub seed: u32,
+    /// Only the typed settings, see above.
+    pub map_gen_settings: MapGenSettings,
+    /// Only the typed settings, see above.
+    pub map_settings: MapSettings,
+    build: u16,
+    /// `autoplace_settings` and `default_enable_all_autoplace_controls`.
+    autoplace_settings: Vec<u8>,
+    area_to_generate_at_start: Vec<u8>,
+    /// `starting_points` and `property_expression_names`.
+    starting_points: Vec<u8>,
+    /// All map settings, the typed ones are overwritten when encoding.
+    raw_map_settings: Vec<u8>,
+}
+
+impl MapExchangeString {
+    pub fn decode(string: &str) -> Result<Self, ServerError> {
+        // pasted strings are often wrapped into multiple lines
+        let string: String = string.split_whitespace().collect();
+        let encoded = string
+            .strip_prefix(PREFIX)
+            .and_then(|string| string.strip_suffix(SUFFIX))
+            .ok_or_else(|| invalid("missing >>> and <<<"))?;
+
+        let compressed = STANDARD
+            .decode(encoded)
+            .map_err(|err| invalid(format!("invalid base64: {}", err)))?;
+        let mut data = vec![];
+        ZlibDecoder::new(compressed.as_slice())
+            .read_to_end(&mut data)
+            .map_err(|err| invalid(format!("invalid zlib data: {}", err)))?;
+
+        let (data, checksum) = data
+            .split_last_chunk::<4>()
+            .ok_or_else(|| invalid("too short"))?;
+        if crc32fast::hash(data) != u32::from_le_bytes(*checksum) {
+            return Err(invalid("checksum mismatch"));
+        }
+
+        Self::read(&mut Reader { data, pos: 0 })
+    }
+
+    /// Build the string for the current settings.
+    pub fn encode(&self) -> String {
+        let mut data = vec![];
+        self.write(&mut data);
+        data.extend_from_slice(&crc32fast::hash(&data).to_le_bytes());
+
+        let mut encoder = ZlibEncoder::new(vec![], Compression::best());
+        encoder
+            .write_all(&data)
+            .expect("writing to a Vec doesn't fail");
+        let compressed = encoder.finish().expect("writing to a Vec doesn't fail");
+
+        format!("{}{}{}", PREFIX, STANDARD.encode(compressed), SUFFIX)
+    }
+
+    fn read(r: &mut Reader) -> Result<Self, ServerError> {
+        let version = Version::from([r.u16()?, r.u16()?, r.u16()?]);
+        let build = r.u16()?;
+        r.u8()?;
+        // water became an autoplace control with 2.0
+        let water_control = version >= Version::from([2, 0, 0]);
+
+        let mut map_gen_settings = MapGenSettings::default();
+        if !water_control {
+            map_gen_settings.water.frequency = r.f32()? as f64;
+            map_gen_settings.water.size = r.f32()? as f64;
+        }
+
+        for _ in 0..r.count()? {
+            let name = r.string()?;
+            let settings =
+                AutoplaceSettings::new(r.f32()? as f64, r.f32()? as f64, r.f32()? as f64);
+            map_gen_settings.autoplace_controls.insert(name, settings);
+        }
+        if water_control && let Some(water) = map_gen_settings.autoplace_controls.remove("water") {
+            map_gen_settings.water = water;
+        }
+
+        let start = r.pos;
+        for _ in 0..r.count()? {
+            r.string()?;
+            r.bool()?;
+            for _ in 0..r.count()? {
+                r.string()?;
+                r.bytes(3 * 4)?;
+            }
+        }
+        r.bool()?;
+        let autoplace_settings = r.data[start..r.pos].to_vec();
+
+        let seed = r.u32()?;
+        map_gen_settings.width = r.u32()?;
+        map_gen_settings.height = r.u32()?;
+        // two positions and an orientation
+        let area_to_generate_at_start = r.bytes(5 * 4)?.to_vec();
+        map_gen_settings.starting_area = r.f32()? as f64;
+        map_gen_settings.peaceful_mode = r.bool()?;
+        if water_control {
+            map_gen_settings.no_enemies_mode = r.bool()?;
+        }
+
+        let start = r.pos;
+        for _ in 0..r.count()? {
+            r.bytes(2 * 4)?;
+        }
+        for _ in 0..r.count()? {
+            r.string()?;
+            r.string()?;
+        }
+        let starting_points = r.data[start..r.pos].to_vec();
+
+        map_gen_settings.cliff_settings = CliffSettings {
+            name: r.string()?,
+            cliff_elevation_0: r.f32()? as f64,
+            cliff_elevation_interval: r.f32()? as f64,
+            richness: r.f32()? as f64,
+        };
+
+        let raw_map_settings = r.data[r.pos..].to_vec();
+        if raw_map_settings.len() < offset::END {
+            return Err(invalid("map settings are incomplete"));
+        }
+
+        Ok(Self {
+            version,
+            seed,
+            map_gen_settings,
+            map_settings: read_map_settings(&raw_map_settings),
+            build,
+            autoplace_settings,
+            area_to_generate_at_start,
+            starting_points,
+            raw_map_settings,
+        })
+    }
+
+    fn write(&self, w: &mut Vec<u8>) {
+        let [major, minor, patch]: [u16; 3] = self.version.into();
+        for part in [major, minor, patch, self.build] {
+            w.extend_from_slice(&part.to_le_bytes());
+        }
+        w.push(0);
+        let water_control = self.version >= Version::from([2, 0, 0]);
+
+        let settings = &self.map_gen_settings;
+        let mut autoplace_controls = settings.autoplace_controls.clone();
+        if water_control {
+            autoplace_controls
+                .entry("water".to_string())
+                .or_insert(settings.water);
+        } else {
+            write_f32(w, settings.water.frequency);
+            write_f32(w, settings.water.size);
+        }
+
+        write_count(w, autoplace_controls.len());
+        for (name, control) in &autoplace_controls {
+            write_string(w, name);
+            write_f32(w, control.frequency);
+            write_f32(w, control.size);
+            write_f32(w, control.richness);
+        }
+        w.extend_from_slice(&self.autoplace_settings);
+
+        w.extend_from_slice(&self.seed.to_le_bytes());
+        w.extend_from_slice(&settings.width.to_le_bytes());
+        w.extend_from_slice(&settings.height.to_le_bytes());
+        w.extend_from_slice(&self.area_to_generate_at_start);
+        write_f32(w, settings.starting_area);
+        w.push(settings.peaceful_mode.into());
+        if water_control {
+            w.push(settings.no_enemies_mode.into());
+        }
+        w.extend_from_slice(&self.starting_points);
+
+        let cliffs = &settings.cliff_settings;
+        write_string(w, &cliffs.name);
+        write_f32(w, cliffs.cliff_elevation_0);
+        write_f32(w, cliffs.cliff_elevation_interval);
+        write_f32(w, cliffs.richness);
+
+        let mut raw_map_settings = self.raw_map_settings.clone();
+        write_map_settings(&mut raw_map_settings, &self.map_settings);
+        w.extend_from_slice(&raw_map_settings);
+    }
+}
+
+impl FromStr for MapExchangeString {
+    type Err = ServerError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::decode(s)
+    }
+}
+
+fn invalid(message: impl Into<String>) -> ServerError {
+    ServerError::InvalidMapExchangeString(message.into())
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], ServerError> {
+        let bytes = self
+            .data
+            .get(self.pos..self.pos + len)
+            .ok_or_else(|| invalid("unexpected end of data"))?;
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], ServerError> {
+        Ok(self.bytes(N)?.try_into().unwrap())
+    }
+
+    fn u8(&mut self) -> Result<u8, ServerError> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn bool(&mut self) -> Result<bool, ServerError> {
+        Ok(self.u8()? != 0)
+    }
+
+    fn u16(&mut self) -> Result<u16, ServerError> {
+        Ok(u16::from_le_bytes(self.array()?))
+    }
+
+    fn u32(&mut self) -> Result<u32, ServerError> {
+        Ok(u32::from_le_bytes(self.array()?))
+    }
+
+    fn f32(&mut self) -> Result<f32, ServerError> {
+        Ok(f32::from_le_bytes(self.array()?))
+    }
+
+    /// Lengths are a single byte, or 255 followed by a u32 for larger ones.
+    fn count(&mut self) -> Result<usize, ServerError> {
+        Ok(match self.u8()? {
+            255 => self.u32()? as usize,
+            count => count as usize,
+        })
+    }
+
+    fn string(&mut self) -> Result<String, ServerError> {
+        let len = self.count()?;
+        String::from_utf8(self.bytes(len)?.to_vec()).map_err(|_| ServerError::Utf8Error())
+    }
+}
+
+fn write_count(w: &mut Vec<u8>, count: usize) {
+    if count < 255 {
+        w.push(count as u8);
+    } else {
+        w.push(255);
+        w.extend_from_slice(&(count as u32).to_le_bytes());
+    }
+}
+
+fn write_string(w: &mut Vec<u8>, string: &str) {
+    write_count(w, string.len());
+    w.extend_from_slice(string.as_bytes());
+}
+
+fn write_f32(w: &mut Vec<u8>, value: f64) {
+    w.extend_from_slice(&(value as f32).to_le_bytes());
+}
+
+fn read_map_settings(raw: &[u8]) -> MapSettings {
+    let bool_at = |pos: usize| raw[pos] != 0;
+    let u32_at = |pos: usize| u32::from_le_bytes(raw[pos..pos + 4].try_into().unwrap());
+    let f64_at = |pos: usize| f64::from_le_bytes(raw[pos..pos + 8].try_into().unwrap());
+
+    let mut settings = MapSettings::default();
+
+    let pollution = &mut settings.pollution;
+    pollution.enabled = bool_at(offset::POLLUTION_ENABLED);
+    pollution.diffusion_ratio = f64_at(offset::DIFFUSION_RATIO);
+    pollution.min_to_diffuse = f64_at(offset::MIN_TO_DIFFUSE);
+    pollution.ageing = f64_at(offset::AGEING);
+    pollution.min_pollution_to_damage_trees = f64_at(offset::MIN_POLLUTION_TO_DAMAGE_TREES);
+    pollution.pollution_restored_per_tree_damage =
+        f64_at(offset::POLLUTION_RESTORED_PER_TREE_DAMAGE);
+    pollution.enemy_attack_pollution_consumption_modifier =
+        f64_at(offset::ENEMY_ATTACK_POLLUTION_CONSUMPTION_MODIFIER);
+
+    let evolution = &mut settings.enemy_evolution;
+    evolution.enabled = bool_at(offset::EVOLUTION_ENABLED);
+    evolution.time_factor = f64_at(offset::TIME_FACTOR);
+    evolution.destroy_factor = f64_at(offset::DESTROY_FACTOR);
+    evolution.pollution_factor = f64_at(offset::POLLUTION_FACTOR);
+
+    let expansion = &mut settings.enemy_expansion;
+    expansion.enabled = bool_at(offset::EXPANSION_ENABLED);
+    expansion.max_expansion_distance = u32_at(offset::MAX_EXPANSION_DISTANCE);
+    expansion.settler_group_min_size = u32_at(offset::SETTLER_GROUP_MIN_SIZE);
+    expansion.settler_group_max_size = u32_at(offset::SETTLER_GROUP_MAX_SIZE);
+    expansion.min_expansion_cooldown = u32_at(offset::MIN_EXPANSION_COOLDOWN);
+    expansion.max_expansion_cooldown = u32_at(offset::MAX_EXPANSION_COOLDOWN);
+
+    settings
+}
+
+fn write_map_settings(raw: &mut [u8], settings: &MapSettings) {
+    let mut put = |pos: usize, bytes: &[u8]| raw[pos..pos + bytes.len()].copy_from_slice(bytes);
+
+    let pollution = &settings.pollution;
+    put(offset::POLLUTION_ENABLED, &[pollution.enabled.into()]);
+    put(
+        offset::DIFFUSION_RATIO,
+        &pollution.diffusion_ratio.to_le_bytes(),
+    );
+    put(
+        offset::MIN_TO_DIFFUSE,
+        &pollution.min_to_diffuse.to_le_bytes(),
+    );
+    put(offset::AGEING, &pollution.ageing.to_le_bytes());
+    put(
+        offset::MIN_POLLUTION_TO_DAMAGE_TREES,
+        &pollution.min_pollution_to_damage_trees.to_le_bytes(),
+    );
+    put(
+        offset::POLLUTION_RESTORED_PER_TREE_DAMAGE,
+        &pollution.pollution_restored_per_tree_damage.to_le_bytes(),
+    );
+    put(
+        offset::ENEMY_ATTACK_POLLUTION_CONSUMPTION_MODIFIER,
+        &pollution
+            .enemy_attack_pollution_consumption_modifier
+            .to_le_bytes(),
+    );
+
+    let evolution = &settings.enemy_evolution;
+    put(offset::EVOLUTION_ENABLED, &[evolution.enabled.into()]);
+    put(offset::TIME_FACTOR, &evolution.time_factor.to_le_bytes());
+    put(
+        offset::DESTROY_FACTOR,
+        &evolution.destroy_factor.to_le_bytes(),
+    );
+    put(
+        offset::POLLUTION_FACTOR,
+        &evolution.pollution_factor.to_le_bytes(),
+    );
+
+    let expansion = &settings.enemy_expansion;
+    put(offset::EXPANSION_ENABLED, &[expansion.enabled.into()]);
+    put(
+        offset::MAX_EXPANSION_DISTANCE,
+        &expansion.max_expansion_distance.to_le_bytes(),
+    );
+    put(
+        offset::SETTLER_GROUP_MIN_SIZE,
+        &expansion.settler_group_min_size.to_le_bytes(),
+    );
+    put(
+        offset::SETTLER_GROUP_MAX_SIZE,
+        &expansion.settler_group_max_size.to_le_bytes(),
+    );
+    put(
+        offset::MIN_EXPANSION_COOLDOWN,
+        &expansion.min_expansion_cooldown.to_le_bytes(),
+    );
+    put(
+        offset::MAX_EXPANSION_COOLDOWN,
+        &expansion.max_expansion_cooldown.to_le_bytes(),
+    );
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::map_settings::EnemyEvolutionSettings;
+
+    /// A string as factorio would build it, with empty untyped parts.
+    fn build(version: [u16; 3]) -> MapExchangeString {
+        let mut map_gen_settings = MapGenSettings::default();
+        map_gen_settings
+            .resource("iron-ore", AutoplaceSettings::new(2.0, 0.5, 1.5))
+            .water(0.5, 2.0)
+            .peaceful_mode(true);
+
+        let mut area = vec![0; 5 * 4];
+        area[..4].copy_from_slice(&(-256i32).to_le_bytes());
+
+        MapExchangeString {
+            version: Version::from(version),
+            seed: 123456,
+            map_gen_settings,
+            map_settings: MapSettings::default(),
+            build: 12345,
+            // no autoplace settings, enable all controls
+            autoplace_settings: vec![0, 1],
+            area_to_generate_at_start: area,
+            // no starting points or expressions
+            starting_points: vec![0, 0],
+            raw_map_settings: vec![0; offset::END + 16],
+        }
+    }
+
+    /// The binary as documented for 1.1, written field by field instead of with `write`,
+    /// every field has a distinct value so a wrong offset reads a neighbour.
+    /// This only checks the code against the documentation, not against strings exported by factorio.
+    fn documented_string(version: [u16; 3]) -> String {
+        fn f32s(w: &mut Vec<u8>, values: &[f32]) {
+            values
+                .iter()
+                .for_each(|value| w.extend(value.to_le_bytes()));
+        }
+        fn f64s(w: &mut Vec<u8>, values: &[f64]) {
+            values
+                .iter()
+                .for_each(|value| w.extend(value.to_le_bytes()));
+        }
+        fn u32s(w: &mut Vec<u8>, values: &[u32]) {
+            values
+                .iter()
+                .for_each(|value| w.extend(value.to_le_bytes()));
+        }
+        fn string(w: &mut Vec<u8>, string: &str) {
+            w.push(string.len() as u8);
+            w.extend(string.as_bytes());
+        }
+        let water_control = version[0] >= 2;
+
+        let mut w = vec![];
+        version.iter().for_each(|part| w.extend(part.to_le_bytes()));
+        w.extend(81u16.to_le_bytes());
+        w.push(0);
+        if !water_control {
+            // terrain_segmentation and water
+            f32s(&mut w, &[0.5, 2.0]);
+        }
+
+        w.push(if water_control { 3 } else { 2 });
+        string(&mut w, "enemy-base");
+        f32s(&mut w, &[1.0, 1.0, 1.0]);
+        string(&mut w, "iron-ore");
+        f32s(&mut w, &[2.0, 0.5, 1.5]);
+        if water_control {
+            string(&mut w, "water");
+            f32s(&mut w, &[0.5, 2.0, 1.0]);
+        }
+        // autoplace_settings: entity with treat_missing_as_default and fish
+        w.push(1);
+        string(&mut w, "entity");
+        w.push(1);
+        w.push(1);
+        string(&mut w, "fish");
+        f32s(&mut w, &[1.0, 1.0, 1.0]);
+        // default_enable_all_autoplace_controls
+        w.push(1);
+
+        // seed, width and height
+        u32s(&mut w, &[3141592653, 2000, 1000]);
+        // area_to_generate_at_start
+        u32s(&mut w, &[(-256i32) as u32, (-256i32) as u32, 256, 256, 0]);
+        f32s(&mut w, &[1.5]);
+        // peaceful_mode
+        w.push(1);
+        if water_control {
+            // no_enemies_mode
+            w.push(0);
+        }
+        // starting_points
+        w.push(1);
+        u32s(&mut w, &[0, 0]);
+        // property_expression_names
+        w.push(1);
+        string(&mut w, "elevation");
+        string(&mut w, "0_17-island");
+        string(&mut w, "cliff");
+        f32s(&mut w, &[10.0, 40.0, 0.5]);
+
+        // pollution
+        w.push(1);
+        f64s(
+            &mut w,
+            &[
+                0.03, 16.0, 1.5, 150.0, 50.0, 61.0, 150.0, 50.0, 11.0, 20.0, 1.25,
+            ],
+        );
+        // steering: default and moving radius, separation factor and force, fuzzy goto
+        for values in [[1.2, 1.2, 0.005], [3.0, 3.0, 0.01]] {
+            f64s(&mut w, &values);
+            w.push(0);
+        }
+        // evolution
+        w.push(1);
+        f64s(&mut w, &[0.000004, 0.002, 0.0000009]);
+        // expansion
+        w.push(0);
+        u32s(&mut w, &[7, 2, 2]);
+        f64s(&mut w, &[0.1, 2.0, 0.5, 0.4, 0.9]);
+        u32s(&mut w, &[5, 20, 14400, 216000]);
+        // unit group, path finder and the rest
+        w.extend([0xab; 16]);
+
+        w.extend(crc32fast::hash(&w).to_le_bytes());
+        let mut encoder = ZlibEncoder::new(vec![], Compression::best());
+        encoder.write_all(&w).unwrap();
+        format!(
+            "{}{}{}",
+            PREFIX,
+            STANDARD.encode(encoder.finish().unwrap()),
+            SUFFIX
+        )
+    }
+
+    #[test]
+    fn documented_layout() {
+        for version in [[1, 1, 110], [2, 0, 28]] {
+            let string = documented_string(version);
+            let decoded = MapExchangeString::decode(&string).unwrap();
+
+            assert_eq!(decoded.version, Version::from(version));
+            assert_eq!(decoded.seed, 3141592653);
+            let settings = &decoded.map_gen_settings;
+            assert_eq!(
+                settings.autoplace_controls["iron-ore"],
+                AutoplaceSettings::new(2.0, 0.5, 1.5)
+            );
+            assert!(!settings.autoplace_controls.contains_key("water"));
+            assert_eq!(settings.water, AutoplaceSettings::new(0.5, 2.0, 1.0));
+            assert_eq!((settings.width, settings.height), (2000, 1000));
+            assert_eq!(settings.starting_area, 1.5);
+            assert!(settings.peaceful_mode);
+            assert_eq!(settings.cliff_settings.richness, 0.5);
+
+            let pollution = &decoded.map_settings.pollution;
+            assert_eq!(pollution.diffusion_ratio, 0.03);
+            assert_eq!(pollution.min_to_diffuse, 16.0);
+            assert_eq!(pollution.ageing, 1.5);
+            assert_eq!(pollution.min_pollution_to_damage_trees, 61.0);
+            assert_eq!(pollution.pollution_restored_per_tree_damage, 11.0);
+            assert_eq!(pollution.enemy_attack_pollution_consumption_modifier, 1.25);
+            assert_eq!(
+                decoded.map_settings.enemy_evolution,
+                EnemyEvolutionSettings::default()
+            );
+            let expansion = &decoded.map_settings.enemy_expansion;
+            assert!(!expansion.enabled);
+            assert_eq!(expansion.max_expansion_distance, 7);
+            assert_eq!(expansion.settler_group_min_size, 5);
+            assert_eq!(expansion.settler_group_max_size, 20);
+            assert_eq!(expansion.min_expansion_cooldown, 14400);
+            assert_eq!(expansion.max_expansion_cooldown, 216000);
+
+            // the untyped parts are written back unchanged
+            assert_eq!(decoded.encode(), string);
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        for version in [[1, 1, 110], [2, 0, 28]] {
+            let exchange = build(version);
+
+            let decoded = MapExchangeString::decode(&exchange.encode()).unwrap();
+            assert_eq!(decoded.map_gen_settings, exchange.map_gen_settings);
+            assert_eq!(decoded.map_settings, exchange.map_settings);
+            assert_eq!(decoded.version, Version::from(version));
+            assert_eq!(decoded.seed, 123456);
+            assert_eq!(decoded.encode(), exchange.encode());
+        }
+    }
+
+    #[test]
+    fn changed_settings() {
+        let mut exchange = build([2, 0, 28]);
+        exchange.map_gen_settings.no_cliffs();
+        exchange.map_settings.no_expansion().no_pollution();
+        exchange.map_settings.enemy_evolution.time_factor = 0.00002;
+
+        // wrapped like when pasted from a ticket
+        let string = exchange.encode();
+        let (start, end) = string.split_at(string.len() / 2);
+        let decoded: MapExchangeString = format!("{}\n  {}\n", start, end).parse().unwrap();
+
+        assert_eq!(decoded.map_gen_settings.cliff_settings.richness, 0.0);
+        assert!(!decoded.map_settings.enemy_expansion.enabled);
+        assert!(!decoded.map_settings.pollution.enabled);
+        assert_eq!(decoded.map_settings.enemy_evolution.time_factor, 0.00002);
+        // untouched raw settings are kept
+        assert_eq!(decoded.raw_map_settings.len(), offset::END + 16);
+    }
+
+    #[test]
+    fn invalid_strings() {
+        assert!(MapExchangeString::decode("eNp").is_err());
+        assert!(MapExchangeString::decode(">>>not base64!<<<").is_err());
+
+        let string = build([1, 1, 110]).encode();
+        let mut data = vec![];
+        ZlibDecoder::new(
+            STANDARD
+                .decode(&string[PREFIX.len()..string.len() - SUFFIX.len()])
+                .unwrap()
+                .as_slice(),
+        )
+        .read_to_end(&mut data)
+        .unwrap();
+        data[20] ^= 1;
+        let mut encoder = ZlibEncoder::new(vec![], Compression::best());
+        encoder.write_all(&data).unwrap();
+        let corrupted = format!(
+            "{}{}{}",
+            PREFIX,
+            STANDARD.encode(encoder.finish().unwrap()),
+            SUFFIX
+        );
+        assert!(matches!(
+            MapExchangeString::decode(&corrupted),
+            Err(ServerError::InvalidMapExchangeString(message)) if message == "checksum mismatch"
+        ));
+    }
+}
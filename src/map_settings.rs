@@ -0,0 +1,332 @@
+use crate::error::ServerError;
+use crate::version::Version;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Size, frequency and richness of something that is placed on the map, 1 is factorio's default.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct AutoplaceSettings {
+    pub frequency: f64,
+    pub size: f64,
+    pub richness: f64,
+}
+
+impl Default for AutoplaceSettings {
+    fn default() -> Self {
+        Self {
+            frequency: 1.0,
+            size: 1.0,
+            richness: 1.0,
+        }
+    }
+}
+
+impl AutoplaceSettings {
+    pub fn new(frequency: f64, size: f64, richness: f64) -> Self {
+        Self {
+            frequency,
+            size,
+            richness,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CliffSettings {
+    pub name: String,
+    /// Elevation of the first row of cliffs.
+    pub cliff_elevation_0: f64,
+    /// Elevation between rows of cliffs, higher means less cliffs.
+    pub cliff_elevation_interval: f64,
+    /// 0 disables cliffs.
+    pub richness: f64,
+}
+
+impl Default for CliffSettings {
+    fn default() -> Self {
+        Self {
+            name: "cliff".to_string(),
+            cliff_elevation_0: 10.0,
+            cliff_elevation_interval: 40.0,
+            richness: 1.0,
+        }
+    }
+}
+
+/// The content of `map-gen-settings.json`, see `data/map-gen-settings.example.json` of a factorio install.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MapGenSettings {
+    /// 0 means infinite.
+    pub width: u32,
+    pub height: u32,
+    pub starting_area: f64,
+    pub peaceful_mode: bool,
+    /// Only supported by factorio 2.0 and later.
+    pub no_enemies_mode: bool,
+    /// Resources like `iron-ore`, as well as `trees` and `enemy-base`. Missing ones use the default.
+    pub autoplace_controls: BTreeMap<String, AutoplaceSettings>,
+    pub cliff_settings: CliffSettings,
+    /// Frequency and size of lakes, richness is ignored.
+    pub water: AutoplaceSettings,
+}
+
+impl Default for MapGenSettings {
+    fn default() -> Self {
+        Self {
+            width: 0,
+            height: 0,
+            starting_area: 1.0,
+            peaceful_mode: false,
+            no_enemies_mode: false,
+            autoplace_controls: BTreeMap::new(),
+            cliff_settings: CliffSettings::default(),
+            water: AutoplaceSettings::default(),
+        }
+    }
+}
+
+impl MapGenSettings {
+    pub fn size(&mut self, width: u32, height: u32) -> &mut Self {
+        self.width = width;
+        self.height = height;
+        self
+    }
+
+    pub fn starting_area(&mut self, starting_area: f64) -> &mut Self {
+        self.starting_area = starting_area;
+        self
+    }
+
+    pub fn peaceful_mode(&mut self, peaceful_mode: bool) -> &mut Self {
+        self.peaceful_mode = peaceful_mode;
+        self
+    }
+
+    pub fn no_enemies_mode(&mut self, no_enemies_mode: bool) -> &mut Self {
+        self.no_enemies_mode = no_enemies_mode;
+        self
+    }
+
+    pub fn autoplace_control(
+        &mut self,
+        name: impl AsRef<str>,
+        settings: AutoplaceSettings,
+    ) -> &mut Self {
+        self.autoplace_controls
+            .insert(name.as_ref().to_string(), settings);
+        self
+    }
+
+    /// A resource like `iron-ore` or `crude-oil`.
+    pub fn resource(&mut self, name: impl AsRef<str>, settings: AutoplaceSettings) -> &mut Self {
+        self.autoplace_control(name, settings)
+    }
+
+    pub fn trees(&mut self, settings: AutoplaceSettings) -> &mut Self {
+        self.autoplace_control("trees", settings)
+    }
+
+    pub fn enemy_bases(&mut self, settings: AutoplaceSettings) -> &mut Self {
+        self.autoplace_control("enemy-base", settings)
+    }
+
+    pub fn cliffs(&mut self, cliff_settings: CliffSettings) -> &mut Self {
+        self.cliff_settings = cliff_settings;
+        self
+    }
+
+    pub fn no_cliffs(&mut self) -> &mut Self {
+        self.cliff_settings.richness = 0.0;
+        self
+    }
+
+    pub fn water(&mut self, frequency: f64, size: f64) -> &mut Self {
+        self.water = AutoplaceSettings::new(frequency, size, 1.0);
+        self
+    }
+
+    /// Build `map-gen-settings.json` for the given factorio version.
+    pub(crate) fn to_json(&self, factorio_version: &Version) -> Result<String, ServerError> {
+        let mut json = serde_json::to_value(self)?;
+        let fields = json
+            .as_object_mut()
+            .expect("map gen settings are serialized as an object");
+        fields.remove("water");
+
+        if factorio_version >= &Version::from([2, 0, 0]) {
+            // water became an autoplace control with 2.0
+            if !self.autoplace_controls.contains_key("water") {
+                fields["autoplace_controls"]["water"] = serde_json::to_value(self.water)?;
+            }
+        } else {
+            fields.remove("no_enemies_mode");
+            fields.insert("water".to_string(), self.water.size.into());
+            fields.insert(
+                "terrain_segmentation".to_string(),
+                self.water.frequency.into(),
+            );
+        }
+
+        Ok(serde_json::to_string_pretty(&json)?)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PollutionSettings {
+    pub enabled: bool,
+    /// Share of the pollution of a chunk that spreads to its neighbours every 64 ticks.
+    pub diffusion_ratio: f64,
+    pub min_to_diffuse: f64,
+    /// How fast pollution is absorbed by tiles.
+    pub ageing: f64,
+    pub enemy_attack_pollution_consumption_modifier: f64,
+    pub min_pollution_to_damage_trees: f64,
+    pub pollution_restored_per_tree_damage: f64,
+}
+
+impl Default for PollutionSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            diffusion_ratio: 0.02,
+            min_to_diffuse: 15.0,
+            ageing: 1.0,
+            enemy_attack_pollution_consumption_modifier: 1.0,
+            min_pollution_to_damage_trees: 60.0,
+            pollution_restored_per_tree_damage: 10.0,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct EnemyEvolutionSettings {
+    pub enabled: bool,
+    /// Evolution per tick.
+    pub time_factor: f64,
+    /// Evolution per destroyed spawner.
+    pub destroy_factor: f64,
+    /// Evolution per unit of pollution.
+    pub pollution_factor: f64,
+}
+
+impl Default for EnemyEvolutionSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            time_factor: 0.000004,
+            destroy_factor: 0.002,
+            pollution_factor: 0.0000009,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct EnemyExpansionSettings {
+    pub enabled: bool,
+    /// In chunks.
+    pub max_expansion_distance: u32,
+    pub settler_group_min_size: u32,
+    pub settler_group_max_size: u32,
+    /// In ticks.
+    pub min_expansion_cooldown: u32,
+    pub max_expansion_cooldown: u32,
+}
+
+impl Default for EnemyExpansionSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_expansion_distance: 7,
+            settler_group_min_size: 5,
+            settler_group_max_size: 20,
+            min_expansion_cooldown: 4 * 3600,
+            max_expansion_cooldown: 60 * 3600,
+        }
+    }
+}
+
+/// The parts of `map-settings.json` that can be configured, factorio uses its defaults for everything else.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MapSettings {
+    pub pollution: PollutionSettings,
+    pub enemy_evolution: EnemyEvolutionSettings,
+    pub enemy_expansion: EnemyExpansionSettings,
+}
+
+impl MapSettings {
+    pub fn pollution(&mut self, pollution: PollutionSettings) -> &mut Self {
+        self.pollution = pollution;
+        self
+    }
+
+    pub fn enemy_evolution(&mut self, enemy_evolution: EnemyEvolutionSettings) -> &mut Self {
+        self.enemy_evolution = enemy_evolution;
+        self
+    }
+
+    pub fn enemy_expansion(&mut self, enemy_expansion: EnemyExpansionSettings) -> &mut Self {
+        self.enemy_expansion = enemy_expansion;
+        self
+    }
+
+    pub fn no_pollution(&mut self) -> &mut Self {
+        self.pollution.enabled = false;
+        self
+    }
+
+    pub fn no_evolution(&mut self) -> &mut Self {
+        self.enemy_evolution.enabled = false;
+        self
+    }
+
+    pub fn no_expansion(&mut self) -> &mut Self {
+        self.enemy_expansion.enabled = false;
+        self
+    }
+
+    pub(crate) fn to_json(&self) -> Result<String, ServerError> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::Value;
+
+    #[test]
+    fn water_by_version() {
+        let mut settings = MapGenSettings::default();
+        settings.water(0.5, 2.0).no_enemies_mode(true);
+
+        let json: Value =
+            serde_json::from_str(&settings.to_json(&Version::from([1, 1, 110])).unwrap()).unwrap();
+        assert_eq!(json["water"], 2.0);
+        assert_eq!(json["terrain_segmentation"], 0.5);
+        assert!(json.get("no_enemies_mode").is_none());
+
+        let json: Value =
+            serde_json::from_str(&settings.to_json(&Version::from([2, 0, 28])).unwrap()).unwrap();
+        assert_eq!(json["autoplace_controls"]["water"]["frequency"], 0.5);
+        assert_eq!(json["autoplace_controls"]["water"]["size"], 2.0);
+        assert!(json.get("water").is_none());
+        assert_eq!(json["no_enemies_mode"], true);
+    }
+
+    #[test]
+    fn autoplace_controls() {
+        let mut settings = MapGenSettings::default();
+        settings
+            .resource("iron-ore", AutoplaceSettings::new(2.0, 1.0, 0.5))
+            .enemy_bases(AutoplaceSettings::new(0.0, 0.0, 0.0))
+            .no_cliffs();
+
+        let json: Value =
+            serde_json::from_str(&settings.to_json(&Version::from([2, 0, 28])).unwrap()).unwrap();
+        assert_eq!(json["autoplace_controls"]["iron-ore"]["richness"], 0.5);
+        assert_eq!(json["autoplace_controls"]["enemy-base"]["size"], 0.0);
+        assert_eq!(json["cliff_settings"]["richness"], 0.0);
+    }
+}
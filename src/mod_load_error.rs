@@ -0,0 +1,183 @@
+use std::fmt::{Display, Formatter};
+
+/// Lines that start one of factorio's mod loading error blocks.
+const BLOCK_STARTS: [&str; 3] = [
+    "Failed to load mods:",
+    "Mods to be disabled:",
+    "Mod checksum mismatch",
+];
+
+/// Factorio refused to load the configured mods, e.g.
+/// ```text
+/// Mods to be disabled:
+///  • RateCalculator: Missing required dependency flib.
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct ModLoadError {
+    /// The mods factorio complained about, empty if none could be recognized.
+    pub mods: Vec<ModFailure>,
+    /// The complete error block as logged.
+    pub message: String,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ModFailure {
+    pub name: String,
+    pub reason: ModFailureReason,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub enum ModFailureReason {
+    /// Name of the dependency that is missing or has the wrong version.
+    MissingDependency(String),
+    /// The mod doesn't support this factorio version.
+    IncompatibleVersion,
+    /// The mod failed while running its lua code, e.g. in the data stage.
+    LuaError(String),
+    ChecksumMismatch,
+    Other(String),
+}
+
+impl ModLoadError {
+    /// Parse a log message, returns `None` if it isn't a mod loading error.
+    pub fn parse(message: &str) -> Option<Self> {
+        let pos = BLOCK_STARTS
+            .iter()
+            .filter_map(|start| message.find(start))
+            .min()?;
+        let message = message[pos..].trim();
+
+        let mut mods: Vec<ModFailure> = vec![];
+        for line in message.lines() {
+            let Some(failure) = Self::parse_line(line) else {
+                continue;
+            };
+            // the lua error and the list of disabled mods name the same mod, the first one is more precise
+            if !mods.iter().any(|known| known.name == failure.name) {
+                mods.push(failure);
+            }
+        }
+
+        Some(Self {
+            mods,
+            message: message.to_string(),
+        })
+    }
+
+    fn parse_line(line: &str) -> Option<ModFailure> {
+        let line = line.trim().trim_start_matches(['•', '-', '*']).trim_start();
+        let line = BLOCK_STARTS
+            .iter()
+            .find_map(|start| line.strip_prefix(start))
+            .unwrap_or(line)
+            .trim();
+
+        // lua errors reference files of the mod: "__RateCalculator__/data.lua:5: ..."
+        if let Some(rest) = line.strip_prefix("__")
+            && let Some((name, _)) = rest.split_once("__/")
+        {
+            return Some(ModFailure {
+                name: name.to_string(),
+                reason: ModFailureReason::LuaError(line.to_string()),
+            });
+        }
+
+        // "RateCalculator: Missing required dependency flib." or "RateCalculator (3.2.7): ..."
+        let (name, detail) = line.split_once(": ")?;
+        let name = name.split(" (").next().unwrap_or(name).trim();
+        if name.is_empty() || name.contains(' ') {
+            return None;
+        }
+
+        Some(ModFailure {
+            name: name.to_string(),
+            reason: ModFailureReason::from_detail(detail.trim()),
+        })
+    }
+}
+
+impl ModFailureReason {
+    fn from_detail(detail: &str) -> Self {
+        let lower = detail.to_ascii_lowercase();
+
+        if let Some(pos) = lower.find("dependency") {
+            let dependency = detail[pos + "dependency".len()..]
+                .trim_start_matches(':')
+                .split_whitespace()
+                .next()
+                .unwrap_or_default()
+                .trim_end_matches('.');
+            return Self::MissingDependency(dependency.to_string());
+        }
+        if lower.contains("checksum") {
+            return Self::ChecksumMismatch;
+        }
+        if lower.contains("incompatible") || lower.contains("version") {
+            return Self::IncompatibleVersion;
+        }
+        Self::Other(detail.to_string())
+    }
+}
+
+impl Display for ModLoadError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn missing_dependency() {
+        let error = ModLoadError::parse(
+            "Mods to be disabled:\n • RateCalculator: Missing required dependency flib >= 0.12.0.\n • OldMod (0.1.0): Incompatible factorio version.",
+        )
+        .unwrap();
+        assert_eq!(
+            error.mods,
+            vec![
+                ModFailure {
+                    name: "RateCalculator".to_string(),
+                    reason: ModFailureReason::MissingDependency("flib".to_string()),
+                },
+                ModFailure {
+                    name: "OldMod".to_string(),
+                    reason: ModFailureReason::IncompatibleVersion,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn lua_error() {
+        let error = ModLoadError::parse(
+            "Failed to load mods: __SomeMod__/data.lua:5: attempt to index global 'foo' (a nil value)\n\nMods to be disabled:\n • SomeMod",
+        )
+        .unwrap();
+        assert_eq!(
+            error.mods,
+            vec![ModFailure {
+                name: "SomeMod".to_string(),
+                reason: ModFailureReason::LuaError(
+                    "__SomeMod__/data.lua:5: attempt to index global 'foo' (a nil value)"
+                        .to_string()
+                ),
+            }]
+        );
+    }
+
+    #[test]
+    fn other() {
+        let error =
+            ModLoadError::parse("Mod checksum mismatch:\nSomeMod: checksum mismatch").unwrap();
+        assert_eq!(error.mods[0].reason, ModFailureReason::ChecksumMismatch);
+
+        assert_eq!(
+            ModLoadError::parse("Loading mod base 2.0.28 (data.lua)"),
+            None
+        );
+    }
+}
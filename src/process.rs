@@ -0,0 +1,161 @@
+use crate::error::ServerError;
+use std::time::Duration;
+use sysinfo::{Pid, ProcessesToUpdate, System};
+use tokio::process::Child;
+use tokio::sync::{mpsc, watch};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum ProcessState {
+    Running,
+    /// The exit code is only known for processes we spawned ourselves.
+    Exited(Option<i32>),
+}
+
+/// Owns the factorio process and publishes when it exits.
+pub(crate) struct ProcessHandle {
+    pid: u32,
+    kill: mpsc::Sender<()>,
+    state: watch::Receiver<ProcessState>,
+}
+
+impl ProcessHandle {
+    /// Take ownership of a spawned child and wait for it to exit.
+    /// On Linux tokio waits on a pidfd if the kernel supports it, no polling involved.
+    pub(crate) fn from_child(mut child: Child) -> Result<Self, ServerError> {
+        let pid = child
+            .id()
+            .ok_or(ServerError::NotAllowed("Process has no pid".into()))?;
+        let (kill_sender, mut kill_recv) = mpsc::channel(1);
+        let (state_sender, state) = watch::channel(ProcessState::Running);
+
+        tokio::spawn(async move {
+            // if the handle is dropped, `recv` returns None and we keep waiting for the process
+            let status = tokio::select! {
+                status = child.wait() => status,
+                Some(()) = kill_recv.recv() => {
+                    child.start_kill().ok();
+                    child.wait().await
+                }
+            };
+
+            let exit_code = status.ok().and_then(|status| status.code());
+            state_sender.send_replace(ProcessState::Exited(exit_code));
+        });
+
+        Ok(Self {
+            pid,
+            kill: kill_sender,
+            state,
+        })
+    }
+
+    /// Watch a process we didn't spawn, e.g. one that survived a restart of the managing process.
+    /// Its exit code can't be collected.
+    pub(crate) fn from_pid(pid: u32) -> Self {
+        let (kill_sender, mut kill_recv) = mpsc::channel(1);
+        let (state_sender, state) = watch::channel(ProcessState::Running);
+
+        tokio::spawn(async move {
+            let exit = wait_for_exit(pid);
+            tokio::pin!(exit);
+            loop {
+                tokio::select! {
+                    _ = &mut exit => break,
+                    Some(()) = kill_recv.recv() => kill(Pid::from_u32(pid)),
+                }
+            }
+
+            state_sender.send_replace(ProcessState::Exited(None));
+        });
+
+        Self {
+            pid,
+            kill: kill_sender,
+            state,
+        }
+    }
+
+    pub(crate) fn pid(&self) -> u32 {
+        self.pid
+    }
+
+    pub(crate) fn subscribe(&self) -> watch::Receiver<ProcessState> {
+        self.state.clone()
+    }
+
+    /// Wait for the process to exit and return its exit code, if known.
+    pub(crate) async fn wait(&self) -> Result<Option<i32>, ServerError> {
+        let mut state = self.state.clone();
+        let state = state
+            .wait_for(|state| matches!(state, ProcessState::Exited(_)))
+            .await?;
+        match *state {
+            ProcessState::Exited(exit_code) => Ok(exit_code),
+            ProcessState::Running => unreachable!(),
+        }
+    }
+
+    /// Kill the process and wait for it to exit.
+    pub(crate) async fn kill(&self) -> Result<Option<i32>, ServerError> {
+        // fails if the process already exited, which is fine
+        self.kill.try_send(()).ok();
+        self.wait().await
+    }
+}
+
+pub(crate) fn is_running(pid: Pid) -> bool {
+    let mut system = System::new();
+    system.refresh_processes(ProcessesToUpdate::Some(&[pid]), true);
+    system.process(pid).is_some()
+}
+
+/// Like `is_running`, but the process also has to be factorio. Pids are reused, e.g. after a reboot.
+pub(crate) fn is_factorio(pid: Pid) -> bool {
+    let mut system = System::new();
+    system.refresh_processes(ProcessesToUpdate::Some(&[pid]), true);
+    system
+        .process(pid)
+        .is_some_and(|process| process.name().to_string_lossy().starts_with("factorio"))
+}
+
+pub(crate) fn kill(pid: Pid) {
+    let mut system = System::new();
+    system.refresh_processes(ProcessesToUpdate::Some(&[pid]), true);
+    if let Some(process) = system.process(pid) {
+        process.kill();
+    }
+}
+
+/// Wait for a process that isn't our child, so `wait` can't be used.
+async fn wait_for_exit(pid: u32) {
+    #[cfg(target_os = "linux")]
+    if let Ok(pidfd) = pidfd::open(pid) {
+        // a pidfd becomes readable when the process exits
+        pidfd.readable().await.ok();
+        return;
+    }
+
+    let mut interval = tokio::time::interval(Duration::from_secs(1));
+    while is_running(Pid::from_u32(pid)) {
+        interval.tick().await;
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod pidfd {
+    use std::io;
+    use std::os::fd::{FromRawFd, OwnedFd};
+    use tokio::io::Interest;
+    use tokio::io::unix::AsyncFd;
+
+    /// Fails on kernels older than 5.3 and if the process doesn't exist.
+    pub(super) fn open(pid: u32) -> io::Result<AsyncFd<OwnedFd>> {
+        // SAFETY: pidfd_open only takes plain values, the returned fd is owned by nobody else
+        let fd = unsafe { libc::syscall(libc::SYS_pidfd_open, pid as libc::pid_t, 0) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let fd = unsafe { OwnedFd::from_raw_fd(fd as i32) };
+        AsyncFd::with_interest(fd, Interest::READABLE)
+    }
+}
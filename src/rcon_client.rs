@@ -0,0 +1,162 @@
+use crate::error::ServerError;
+use rcon::Connection;
+use std::io;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::net::TcpStream;
+use tokio::sync::Mutex;
+use tokio::time::timeout;
+
+/// One RCON connection per server, shared by all callers.
+/// Commands are executed one after another, the connection is (re)established on demand.
+pub(crate) struct RconClient {
+    address: SocketAddr,
+    password: String,
+    /// Limit for connecting and for each command, the connection is dropped when it is reached.
+    timeout: Duration,
+    connection: Mutex<Option<RconConnection>>,
+}
+
+struct RconConnection {
+    connection: Connection<TrackedStream>,
+    write_failed: Arc<AtomicBool>,
+}
+
+/// Remembers if writing failed, so a command is only retried if it never reached factorio.
+struct TrackedStream {
+    stream: TcpStream,
+    write_failed: Arc<AtomicBool>,
+}
+
+impl TrackedStream {
+    fn track<T>(&self, result: Poll<io::Result<T>>) -> Poll<io::Result<T>> {
+        if let Poll::Ready(Err(_)) = &result {
+            self.write_failed.store(true, Ordering::SeqCst);
+        }
+        result
+    }
+}
+
+impl AsyncRead for TrackedStream {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.stream).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for TrackedStream {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let result = Pin::new(&mut self.stream).poll_write(cx, buf);
+        self.track(result)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let result = Pin::new(&mut self.stream).poll_flush(cx);
+        self.track(result)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.stream).poll_shutdown(cx)
+    }
+}
+
+impl RconConnection {
+    async fn cmd(&mut self, command: &str, limit: Duration) -> Result<String, ServerError> {
+        self.write_failed.store(false, Ordering::SeqCst);
+        timeout(limit, self.connection.cmd(command))
+            .await
+            .map_err(|_| ServerError::RconTimeout)?
+            .map_err(ServerError::from)
+    }
+
+    /// Whether the last command failed before it was completely sent.
+    fn write_failed(&self) -> bool {
+        self.write_failed.load(Ordering::SeqCst)
+    }
+}
+
+impl RconClient {
+    pub(crate) fn new(address: SocketAddr, password: impl AsRef<str>, timeout: Duration) -> Self {
+        Self {
+            address,
+            password: password.as_ref().to_string(),
+            timeout,
+            connection: Mutex::new(None),
+        }
+    }
+
+    /// Execute a command and return the response.
+    pub(crate) async fn cmd(&self, command: &str) -> Result<String, ServerError> {
+        let mut connection = self.connection.lock().await;
+
+        if let Some(existing) = connection.as_mut() {
+            match existing.cmd(command, self.timeout).await {
+                Ok(response) => return Ok(response),
+                // the connection broke (e.g. factorio restarted) before the command was sent,
+                // try once more with a new one
+                Err(ServerError::Rcon(rcon::Error::Io(_))) if existing.write_failed() => {
+                    *connection = None
+                }
+                // the command may have run, so it isn't repeated
+                Err(err @ (ServerError::Rcon(rcon::Error::Io(_)) | ServerError::RconTimeout)) => {
+                    *connection = None;
+                    return Err(err);
+                }
+                Err(err) => return Err(err),
+            }
+        }
+
+        let new_connection = connection.insert(self.connect().await?);
+        match new_connection.cmd(command, self.timeout).await {
+            Ok(response) => Ok(response),
+            Err(err) => {
+                *connection = None;
+                Err(err)
+            }
+        }
+    }
+
+    /// Drop the current connection, the next command will connect again.
+    pub(crate) async fn disconnect(&self) {
+        self.connection.lock().await.take();
+    }
+
+    /// Fails with `RconUnavailable` if no connection could be established.
+    async fn connect(&self) -> Result<RconConnection, ServerError> {
+        let write_failed = Arc::new(AtomicBool::new(false));
+        let connect = async {
+            let stream = TcpStream::connect(self.address).await?;
+            let stream = TrackedStream {
+                stream,
+                write_failed: write_failed.clone(),
+            };
+            Ok::<_, ServerError>(
+                <Connection<TrackedStream>>::builder()
+                    .enable_factorio_quirks(true)
+                    .handshake(stream, self.password.as_str())
+                    .await?,
+            )
+        };
+        let connection = timeout(self.timeout, connect)
+            .await
+            .unwrap_or(Err(ServerError::RconTimeout))
+            .map_err(|err| ServerError::RconUnavailable(err.to_string()))?;
+
+        Ok(RconConnection {
+            connection,
+            write_failed,
+        })
+    }
+}
@@ -0,0 +1,156 @@
+use crate::credentials::Credentials;
+use crate::error::ServerError;
+use crate::version::Version;
+use serde::{Deserialize, Serialize};
+
+/// Settings that are only known to factorio 2.0 and later.
+const SETTINGS_SINCE_2_0: [&str; 1] = ["auto_pause_when_players_connect"];
+
+/// The content of `server-settings.json`, see `data/server-settings.example.json` of a factorio install.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ServerSettings {
+    /// Shown in the server browser.
+    pub name: String,
+    pub description: String,
+    pub tags: Vec<String>,
+    /// 0 means unlimited.
+    pub max_players: u32,
+    pub visibility: Visibility,
+    /// Empty for no password.
+    pub game_password: String,
+    /// Only allow players with a factorio.com account.
+    pub require_user_verification: bool,
+    pub allow_commands: AllowCommands,
+    /// Minutes between autosaves, 0 disables them.
+    pub autosave_interval: u32,
+    pub autosave_slots: u32,
+    /// Minutes until idle players are kicked, 0 disables it.
+    pub afk_autokick_interval: u32,
+    /// Pause while no players are connected.
+    pub auto_pause: bool,
+    /// Pause while a player is joining, only supported by factorio 2.0 and later.
+    pub auto_pause_when_players_connect: bool,
+    pub only_admins_can_pause_the_game: bool,
+    pub autosave_only_on_server: bool,
+    pub non_blocking_saving: bool,
+}
+
+impl Default for ServerSettings {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            description: String::new(),
+            tags: vec![],
+            max_players: 0,
+            // public servers need factorio.com credentials
+            visibility: Visibility {
+                public: false,
+                lan: true,
+            },
+            game_password: String::new(),
+            require_user_verification: true,
+            allow_commands: AllowCommands::AdminsOnly,
+            autosave_interval: 10,
+            autosave_slots: 5,
+            afk_autokick_interval: 0,
+            auto_pause: true,
+            auto_pause_when_players_connect: false,
+            only_admins_can_pause_the_game: true,
+            autosave_only_on_server: true,
+            non_blocking_saving: false,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Visibility {
+    /// List the server in the public server browser, requires a login with `Cache::factorio_login`.
+    pub public: bool,
+    pub lan: bool,
+}
+
+/// Who can run lua commands.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AllowCommands {
+    #[serde(rename = "true")]
+    Everyone,
+    #[serde(rename = "admins-only")]
+    AdminsOnly,
+    #[serde(rename = "false")]
+    Nobody,
+}
+
+impl ServerSettings {
+    /// Build `server-settings.json` for the given factorio version.
+    /// Public servers are announced with the `credentials`.
+    pub(crate) fn to_json(
+        &self,
+        factorio_version: &Version,
+        credentials: Option<&Credentials>,
+    ) -> Result<String, ServerError> {
+        let mut json = serde_json::to_value(self)?;
+        let fields = json
+            .as_object_mut()
+            .expect("server settings are serialized as an object");
+
+        if factorio_version < &Version::from([2, 0, 0]) {
+            for name in SETTINGS_SINCE_2_0 {
+                fields.remove(name);
+            }
+        }
+
+        if self.visibility.public {
+            let credentials = credentials.ok_or(ServerError::NotAllowed(
+                "public servers require credentials".to_string(),
+            ))?;
+            fields.insert("username".to_string(), credentials.username.clone().into());
+            fields.insert("token".to_string(), credentials.token.clone().into());
+        }
+
+        Ok(serde_json::to_string_pretty(&json)?)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::Value;
+
+    #[test]
+    fn version_fields() {
+        let settings = ServerSettings::default();
+
+        let json: Value =
+            serde_json::from_str(&settings.to_json(&Version::from([1, 1, 110]), None).unwrap())
+                .unwrap();
+        assert!(json.get("auto_pause_when_players_connect").is_none());
+        assert_eq!(json["allow_commands"], "admins-only");
+
+        let json: Value =
+            serde_json::from_str(&settings.to_json(&Version::from([2, 0, 28]), None).unwrap())
+                .unwrap();
+        assert_eq!(json["auto_pause_when_players_connect"], false);
+        assert!(json.get("token").is_none());
+    }
+
+    #[test]
+    fn public_credentials() {
+        let mut settings = ServerSettings::default();
+        settings.visibility.public = true;
+        assert!(settings.to_json(&Version::from([2, 0, 28]), None).is_err());
+
+        let credentials = Credentials {
+            username: "some_player".to_string(),
+            token: "secret".to_string(),
+        };
+        let json: Value = serde_json::from_str(
+            &settings
+                .to_json(&Version::from([2, 0, 28]), Some(&credentials))
+                .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(json["username"], "some_player");
+        assert_eq!(json["token"], "secret");
+    }
+}
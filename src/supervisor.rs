@@ -0,0 +1,168 @@
+use crate::error::ServerError;
+use crate::event::{GameState, ServerEvent};
+use crate::instance::{
+    InstanceSettings, ProcessRuntime, SharedRuntime, Status, StatusUpdate, set_status,
+};
+use crate::manager::Manager;
+use crate::mod_load_error::ModLoadError;
+use crate::rcon_client::RconClient;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::sync::broadcast;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::watch::Sender;
+use tokio::time::{Instant, sleep};
+
+/// Follows the events of a server to keep its status up to date,
+/// and restarts it after a crash if a `RestartPolicy` is configured.
+pub(crate) struct Supervisor {
+    pub(crate) settings: InstanceSettings,
+    pub(crate) path: PathBuf,
+    pub(crate) name: String,
+    pub(crate) manager: Manager,
+    pub(crate) events: broadcast::Sender<ServerEvent>,
+    pub(crate) runtime: SharedRuntime,
+    /// Disconnected when the process exits, a restarted server needs a new connection.
+    pub(crate) rcon: Option<Arc<RconClient>>,
+    pub(crate) status: Sender<StatusUpdate>,
+    pub(crate) mod_load_error: Arc<Mutex<Option<ModLoadError>>>,
+    pub(crate) crash_count: Arc<AtomicU32>,
+}
+
+impl Supervisor {
+    pub(crate) async fn run(
+        self,
+        mut recv: broadcast::Receiver<ServerEvent>,
+    ) -> Result<(), ServerError> {
+        // restarts in a row, without the server running for `reset_after`
+        let mut retries = 0;
+        let mut running_since = None;
+        // a restarted server that dies while loading crashed again
+        let mut restarted = false;
+
+        loop {
+            let event = match recv.recv().await {
+                Ok(event) => event,
+                // we only care about the latest state, missed events are fine
+                Err(RecvError::Lagged(_)) => continue,
+                Err(err) => return Err(err.into()),
+            };
+
+            match event {
+                ServerEvent::ProcessExited { .. } => {
+                    if let Some(rcon) = &self.rcon {
+                        rcon.disconnect().await;
+                    }
+
+                    // everything else is a requested stop or a failed start
+                    let crashed = match self.status.borrow().status {
+                        Status::Running => true,
+                        Status::Starting => restarted,
+                        _ => false,
+                    };
+                    if let Some(since) = running_since.take()
+                        && self
+                            .settings
+                            .restart_policy
+                            .as_ref()
+                            .is_some_and(|policy| Instant::now() - since >= policy.reset_after)
+                    {
+                        retries = 0;
+                    }
+
+                    if !crashed || !self.restart(&mut retries).await {
+                        set_status(&self.status, Status::Stopped);
+                        self.manager.unregister_instance(&self.name, &self.runtime);
+                        break;
+                    }
+                    restarted = true;
+                }
+                ServerEvent::StateChanged {
+                    from: GameState::CreatingGame,
+                    to: GameState::InGame,
+                } => {
+                    set_status(&self.status, Status::Running);
+                    running_since = Some(Instant::now());
+                    restarted = false;
+                }
+                ServerEvent::StateChanged {
+                    from: GameState::Disconnected,
+                    to: GameState::Closed,
+                } => {
+                    set_status(&self.status, Status::Closed);
+                }
+                ServerEvent::ModLoadError(error) => {
+                    *self.mod_load_error.lock().unwrap() = Some(error);
+                }
+                _ => {}
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Start a new process after a crash, returns false if the policy doesn't allow it.
+    async fn restart(&self, retries: &mut u32) -> bool {
+        let Some(policy) = &self.settings.restart_policy else {
+            return false;
+        };
+
+        self.crash_count.fetch_add(1, Ordering::Relaxed);
+        set_status(&self.status, Status::Starting);
+
+        // keep the log of the crashed process, the new one would overwrite it
+        let backup = self
+            .manager
+            .backup_files(
+                &self.name,
+                vec![
+                    self.path.join("factorio-current.log"),
+                    self.path.join("factorio-previous.log"),
+                    self.path.join("console.log"),
+                ],
+            )
+            .await;
+        if let Err(err) = backup {
+            self.events
+                .send(ServerEvent::LogBackupFailed {
+                    error: err.to_string(),
+                })
+                .ok();
+        }
+
+        while *retries < policy.max_retries {
+            sleep(policy.backoff(*retries)).await;
+            *retries += 1;
+
+            // stopped or killed while waiting
+            if self.status.borrow().status != Status::Starting {
+                return false;
+            }
+
+            let settings = self.settings.restart_settings(&self.path);
+            match ProcessRuntime::spawn(&settings, &self.path, self.events.clone()).await {
+                Ok(runtime) => {
+                    let runtime = Arc::new(runtime);
+                    *self.runtime.lock().unwrap() = runtime.clone();
+                    // stopped or killed while spawning, the new process must not outlive that.
+                    // Checked after installing it, so a stop that comes later sees the new process.
+                    if self.status.borrow().status != Status::Starting {
+                        runtime.process.kill().await.ok();
+                        return false;
+                    }
+                    return true;
+                }
+                Err(err) => {
+                    self.events
+                        .send(ServerEvent::RestartFailed {
+                            error: err.to_string(),
+                        })
+                        .ok();
+                }
+            }
+        }
+
+        false
+    }
+}
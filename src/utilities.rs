@@ -1,7 +1,7 @@
 use crate::error::ServerError;
 use std::fs::Metadata;
 use std::io;
-use std::net::IpAddr;
+use std::net::{IpAddr, SocketAddr};
 use std::path::Path;
 use tokio::net::TcpListener;
 
@@ -18,8 +18,16 @@ pub(crate) fn get_file_size(metadata: Metadata) -> u64 {
     }
 }
 
-pub(crate) async fn get_random_port(addr: IpAddr) -> Result<u16, ServerError> {
-    let listener = TcpListener::bind((addr, 0)).await?;
+/// Check that `port` can be bound on `addr`, port 0 picks a random free port.
+/// Fails if the address doesn't belong to this host or the port is already in use.
+pub(crate) async fn get_free_port(addr: IpAddr, port: u16) -> Result<u16, ServerError> {
+    let listener = TcpListener::bind((addr, port)).await.map_err(|err| {
+        ServerError::InvalidAddress(format!(
+            "can't bind to {}: {}",
+            SocketAddr::new(addr, port),
+            err
+        ))
+    })?;
 
     let port = listener.local_addr()?.port();
 
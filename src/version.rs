@@ -1,4 +1,5 @@
 use crate::error::ServerError;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::fmt::{Display, Formatter};
 use std::str::FromStr;
 
@@ -11,6 +12,12 @@ impl From<[u16; 3]> for Version {
     }
 }
 
+impl From<Version> for [u16; 3] {
+    fn from(value: Version) -> Self {
+        value.0
+    }
+}
+
 impl FromStr for Version {
     type Err = ServerError;
 
@@ -38,3 +45,17 @@ impl Display for Version {
         write!(f, "{}.{}.{}", self.0[0], self.0[1], self.0[2])
     }
 }
+
+impl Serialize for Version {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Version {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}